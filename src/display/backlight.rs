@@ -0,0 +1,294 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::peripheral::lcds::{Lcds, Result};
+use crate::peripheral::transport::Transport;
+
+/// How long a wake keeps the backlight on when no idle timeout is set.
+pub const DEFAULT_WAKE: Duration = Duration::from_secs(30);
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// A wall-clock time of day with minute resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    /// Returns the time `hour:minute`, or `None` if either is out of range.
+    pub fn new(hour: u8, minute: u8) -> Option<Self> {
+        (hour < 24 && minute < 60).then(|| Self(hour as u16 * 60 + minute as u16))
+    }
+
+    /// Parses `HH:MM`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (hour, minute) = text.trim().split_once(':')?;
+        Self::new(hour.parse().ok()?, minute.parse().ok()?)
+    }
+
+    /// Returns the local time of day for a Unix timestamp, given the local
+    /// offset from UTC in minutes.
+    pub fn from_unix(secs: u64, utc_offset_minutes: i32) -> Self {
+        let minutes = (secs / 60) as i64 + utc_offset_minutes as i64;
+        Self(minutes.rem_euclid(MINUTES_PER_DAY as i64) as u16)
+    }
+
+    /// Returns the minutes since midnight.
+    pub fn minutes(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+/// A nightly window during which the backlight stays off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    /// Time the backlight turns off.
+    pub off_at: TimeOfDay,
+    /// Time the backlight turns back on. May be earlier than `off_at`, in
+    /// which case the window spans midnight.
+    pub on_at: TimeOfDay,
+}
+
+impl Schedule {
+    /// Creates a window from `off_at` until `on_at`.
+    pub fn new(off_at: TimeOfDay, on_at: TimeOfDay) -> Self {
+        Self { off_at, on_at }
+    }
+
+    /// Parses `HH:MM-HH:MM`, e.g. `22:00-07:00`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (off_at, on_at) = text.split_once('-')?;
+        Some(Self::new(TimeOfDay::parse(off_at)?, TimeOfDay::parse(on_at)?))
+    }
+
+    /// Returns true if `time` falls inside the off window.
+    pub fn is_off(&self, time: TimeOfDay) -> bool {
+        if self.off_at <= self.on_at {
+            self.off_at <= time && time < self.on_at
+        } else {
+            time >= self.off_at || time < self.on_at
+        }
+    }
+}
+
+/// Light levels at which the surroundings count as dark.
+///
+/// The two thresholds form a hysteresis band so a reading hovering around
+/// one value does not flicker the backlight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ambient {
+    /// Readings below this turn the backlight off.
+    pub dark_below: f32,
+    /// Readings above this turn it back on.
+    pub bright_above: f32,
+}
+
+/// Decides when the backlight should be lit and switches it accordingly.
+///
+/// The backlight is off while the schedule's night window is active, while
+/// the ambient light is dark, or once the idle timeout has passed without
+/// activity. `wake` (for a button press or an alert) lights it regardless
+/// for the idle timeout, or `DEFAULT_WAKE` if none is set. The display
+/// itself is left on either way.
+#[derive(Debug, Clone)]
+pub struct BacklightController {
+    schedule: Option<Schedule>,
+    ambient: Option<Ambient>,
+    idle_timeout: Option<Duration>,
+    last_activity: Option<Instant>,
+    dark: bool,
+    lit: Option<bool>,
+}
+
+impl Default for BacklightController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BacklightController {
+    /// Creates a controller that keeps the backlight on.
+    pub fn new() -> Self {
+        Self {
+            schedule: None,
+            ambient: None,
+            idle_timeout: None,
+            last_activity: None,
+            dark: false,
+            lit: None,
+        }
+    }
+
+    /// Turns the backlight off during the schedule's window.
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    /// Turns the backlight off while the measured light level is dark.
+    pub fn with_ambient(mut self, ambient: Ambient) -> Self {
+        self.ambient = Some(ambient);
+        self
+    }
+
+    /// Turns the backlight off after `timeout` without activity.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the state last written to the display, if any.
+    pub fn is_lit(&self) -> Option<bool> {
+        self.lit
+    }
+
+    /// Records activity that should light the backlight.
+    ///
+    /// Returns true if the backlight was off, so a button press that only
+    /// woke the display can be swallowed instead of acting on the UI.
+    pub fn wake(&mut self, now: Instant) -> bool {
+        self.last_activity = Some(now);
+        self.lit != Some(true)
+    }
+
+    /// Feeds a light level reading into the ambient hysteresis.
+    pub fn observe_light(&mut self, level: f32) {
+        if let Some(ambient) = self.ambient {
+            if level < ambient.dark_below {
+                self.dark = true;
+            } else if level > ambient.bright_above {
+                self.dark = false;
+            }
+        }
+    }
+
+    /// Returns whether the backlight should be lit.
+    pub fn wanted(&self, now: Instant, time: TimeOfDay) -> bool {
+        let wake_time = self.idle_timeout.unwrap_or(DEFAULT_WAKE);
+        let awake = self.last_activity.is_some_and(|t| now.duration_since(t) < wake_time);
+        if awake {
+            return true;
+        }
+        let night = self.schedule.is_some_and(|s| s.is_off(time));
+        let idle = self.idle_timeout.is_some();
+        !(night || self.dark || idle)
+    }
+
+    /// Switches the backlight if its wanted state changed.
+    /// Returns true if a command was sent.
+    pub fn update<T: Transport>(&mut self, lcds: &mut Lcds<T>, now: Instant, time: TimeOfDay) -> Result<bool> {
+        // Start-up counts as activity, so the backlight lights briefly at boot.
+        self.last_activity.get_or_insert(now);
+        let lit = self.wanted(now, time);
+        if self.lit == Some(lit) {
+            return Ok(false);
+        }
+        debug!("backlight {} at {}", if lit { "on" } else { "off" }, time);
+        lcds.display_set(true, lit)?;
+        self.lit = Some(lit);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+
+    fn at(text: &str) -> TimeOfDay {
+        TimeOfDay::parse(text).unwrap()
+    }
+
+    const NOON: &str = "12:00";
+
+    #[test]
+    fn time_of_day_parses_and_converts() {
+        assert_eq!(at("07:05").minutes(), 7 * 60 + 5);
+        assert_eq!(at(" 23:59 ").to_string(), "23:59");
+        assert_eq!(TimeOfDay::parse("24:00"), None);
+        assert_eq!(TimeOfDay::parse("12:60"), None);
+        assert_eq!(TimeOfDay::parse("noon"), None);
+        // 1970-01-02 00:30 UTC is 23:30 the day before at UTC-1.
+        assert_eq!(TimeOfDay::from_unix(86_400 + 30 * 60, -60), at("23:30"));
+        assert_eq!(TimeOfDay::from_unix(0, 120), at("02:00"));
+    }
+
+    #[test]
+    fn schedule_can_span_midnight() {
+        let night = Schedule::parse("22:00-07:00").unwrap();
+        for off in ["22:00", "23:30", "00:00", "03:15", "06:59"] {
+            assert!(night.is_off(at(off)), "{}", off);
+        }
+        for on in ["07:00", NOON, "21:59"] {
+            assert!(!night.is_off(at(on)), "{}", on);
+        }
+    }
+
+    #[test]
+    fn schedule_within_a_day() {
+        let siesta = Schedule::parse("13:00-15:00").unwrap();
+        assert!(siesta.is_off(at("14:00")));
+        assert!(!siesta.is_off(at("15:00")));
+        assert!(!siesta.is_off(at("23:00")));
+        assert_eq!(Schedule::parse("13:00"), None);
+    }
+
+    #[test]
+    fn ambient_light_has_hysteresis() {
+        let mut controller = BacklightController::new().with_ambient(Ambient { dark_below: 10.0, bright_above: 30.0 });
+        let later = Instant::now() + DEFAULT_WAKE;
+        let wanted = |c: &BacklightController| c.wanted(later, at(NOON));
+        controller.observe_light(20.0);
+        assert!(wanted(&controller));
+        controller.observe_light(5.0);
+        assert!(!wanted(&controller));
+        controller.observe_light(20.0);
+        assert!(!wanted(&controller), "still dark inside the band");
+        controller.observe_light(35.0);
+        assert!(wanted(&controller));
+        controller.observe_light(20.0);
+        assert!(wanted(&controller), "still bright inside the band");
+    }
+
+    #[test]
+    fn wake_overrides_the_night_window() {
+        let mut controller = BacklightController::new().with_schedule(Schedule::parse("22:00-07:00").unwrap());
+        let start = Instant::now();
+        let night = at("23:00");
+        assert!(!controller.wanted(start, night));
+        controller.wake(start);
+        assert!(controller.wanted(start + DEFAULT_WAKE / 2, night));
+        assert!(!controller.wanted(start + DEFAULT_WAKE, night));
+    }
+
+    #[test]
+    fn idle_timeout_turns_the_backlight_off_until_woken() {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        let timeout = Duration::from_secs(60);
+        let mut controller = BacklightController::new().with_idle_timeout(timeout);
+        let start = Instant::now();
+
+        // Start-up counts as activity.
+        assert!(controller.update(&mut lcds, start, at(NOON)).unwrap());
+        assert!(lcds.transport().backlight_on());
+        assert!(!controller.update(&mut lcds, start + timeout / 2, at(NOON)).unwrap());
+
+        assert!(controller.update(&mut lcds, start + timeout, at(NOON)).unwrap());
+        assert_eq!(controller.is_lit(), Some(false));
+        assert!(!lcds.transport().backlight_on());
+        assert!(lcds.transport().display_on());
+
+        let pressed = start + timeout * 2;
+        assert!(controller.wake(pressed), "a press that only wakes is reported");
+        assert!(controller.update(&mut lcds, pressed, at(NOON)).unwrap());
+        assert!(lcds.transport().backlight_on());
+        assert!(!controller.wake(pressed), "already lit");
+    }
+}
@@ -0,0 +1,288 @@
+use log::debug;
+use prometheus::{IntCounter, Opts, Registry};
+
+use crate::peripheral::charset::Charset;
+use crate::peripheral::lcds::{DEFAULT_DEVICE, Lcds, Result};
+use crate::peripheral::transport::Transport;
+
+/// Number of rows on the PmodCLS.
+pub const ROWS: usize = 2;
+
+/// Maximum number of columns the controller can address per row.
+pub const MAX_COLS: usize = 40;
+
+/// Bytes a `set_pos` command costs on the wire (`ESC [ r ; c c H`).
+const SET_POS_LEN: usize = 7;
+
+/// Byte counts for a single `flush`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlushStats {
+    /// Bytes written to the transport, commands included.
+    pub bytes_sent: usize,
+    /// Bytes a full redraw would have cost on top of `bytes_sent`.
+    pub bytes_saved: usize,
+    /// Number of changed runs that were written.
+    pub runs: usize,
+}
+
+/// A double-buffered, off-screen copy of the display.
+///
+/// Draw into the back buffer with `write_str`/`put`, then call `flush` to
+/// send only the cells that differ from what the panel already shows. Runs
+/// separated by fewer unchanged cells than a cursor move costs are merged.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    cols: usize,
+    back: [[u8; MAX_COLS]; ROWS],
+    front: [[u8; MAX_COLS]; ROWS],
+    front_valid: bool,
+    charset: Charset,
+    metrics: Metrics,
+}
+
+/// Flush counters of one display, labelled with its `device` name.
+#[derive(Debug, Clone)]
+struct Metrics {
+    bytes_sent: IntCounter,
+    bytes_saved: IntCounter,
+}
+
+impl Metrics {
+    fn new(device: &str) -> Self {
+        let counter = |name: &str, help: &str| {
+            IntCounter::with_opts(Opts::new(name, help).const_label("device", device)).unwrap()
+        };
+        Self {
+            bytes_sent: counter("lcds_framebuffer_bytes_sent_total", "Bytes sent by framebuffer flushes"),
+            bytes_saved: counter(
+                "lcds_framebuffer_bytes_saved_total",
+                "Bytes saved by framebuffer flushes compared to full redraws",
+            ),
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Creates a blank framebuffer for a panel `cols` characters wide.
+    ///
+    /// The first `flush` always redraws every cell.
+    pub fn new(cols: usize) -> Self {
+        Self {
+            cols: cols.clamp(1, MAX_COLS),
+            back: [[b' '; MAX_COLS]; ROWS],
+            front: [[b' '; MAX_COLS]; ROWS],
+            front_valid: false,
+            charset: Charset::new(),
+            metrics: Metrics::new(DEFAULT_DEVICE),
+        }
+    }
+
+    /// Sets the charset `write_str` encodes with, e.g. the display driver's.
+    pub fn with_charset(mut self, charset: Charset) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Labels the counters with the `device` name of the display flushed to,
+    /// so framebuffers of several displays can share a registry.
+    ///
+    /// Replaces the counters with fresh ones, so call it before registering.
+    pub fn with_device(mut self, device: &str) -> Self {
+        self.metrics = Metrics::new(device);
+        self
+    }
+
+    /// Registers the framebuffer counters with a Prometheus registry.
+    pub fn register_metrics(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.metrics.bytes_sent.clone()))?;
+        registry.register(Box::new(self.metrics.bytes_saved.clone()))
+    }
+
+    /// Returns the number of columns per row.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns the back buffer contents of a row.
+    pub fn row(&self, row: usize) -> &[u8] {
+        &self.back[row][..self.cols]
+    }
+
+    /// Returns the total bytes saved by all flushes so far.
+    pub fn total_bytes_saved(&self) -> u64 {
+        self.metrics.bytes_saved.get()
+    }
+
+    /// Fills the back buffer with spaces.
+    pub fn clear(&mut self) {
+        for row in self.back.iter_mut() {
+            row.fill(b' ');
+        }
+    }
+
+    /// Sets a single cell in the back buffer. Out-of-range cells are ignored.
+    pub fn put(&mut self, row: usize, col: usize, code: u8) {
+        if row < ROWS && col < self.cols {
+            self.back[row][col] = code;
+        }
+    }
+
+    /// Writes character codes into the back buffer, truncated at the row end.
+    pub fn write_bytes(&mut self, row: usize, col: usize, codes: &[u8]) {
+        for (i, &code) in codes.iter().enumerate() {
+            self.put(row, col + i, code);
+        }
+    }
+
+    /// Writes a string into the back buffer, truncated at the row end.
+    ///
+    /// Characters the charset cannot map are shown as `charset::REPLACEMENT`.
+    pub fn write_str(&mut self, row: usize, col: usize, text: &str) {
+        let codes = self.charset.encode_lossy(text);
+        self.write_bytes(row, col, &codes);
+    }
+
+    /// Forgets what the panel shows so the next `flush` redraws everything,
+    /// e.g. after the display was reset or cleared behind our back.
+    pub fn invalidate(&mut self) {
+        self.front_valid = false;
+    }
+
+    /// Sends the cells that changed since the last flush to the display.
+    ///
+    /// # Errors
+    /// * Returns the driver error if a write fails; the framebuffer is then
+    ///   invalidated so the next flush redraws everything.
+    pub fn flush<T: Transport>(&mut self, lcds: &mut Lcds<T>) -> Result<FlushStats> {
+        let mut stats = FlushStats::default();
+        let sent = lcds.batch(|lcds| {
+            for row in 0..ROWS {
+                for (start, end) in self.changed_runs(row) {
+                    lcds.set_pos(row as u8, start as u8)?;
+                    lcds.write_data(&self.back[row][start..end])?;
+                    stats.bytes_sent += SET_POS_LEN + (end - start);
+                    stats.runs += 1;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = sent {
+            self.front_valid = false;
+            return Err(e);
+        }
+        self.front = self.back;
+        self.front_valid = true;
+
+        let full_redraw = ROWS * (SET_POS_LEN + self.cols);
+        stats.bytes_saved = full_redraw.saturating_sub(stats.bytes_sent);
+        self.metrics.bytes_sent.inc_by(stats.bytes_sent as u64);
+        self.metrics.bytes_saved.inc_by(stats.bytes_saved as u64);
+        debug!("framebuffer flush: {:?}", stats);
+        Ok(stats)
+    }
+
+    /// Returns the half-open column ranges of a row that need sending.
+    fn changed_runs(&self, row: usize) -> Vec<(usize, usize)> {
+        if !self.front_valid {
+            return vec![(0, self.cols)];
+        }
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        let back = &self.back[row][..self.cols];
+        let front = &self.front[row][..self.cols];
+        for col in (0..self.cols).filter(|&c| back[c] != front[c]) {
+            match runs.last_mut() {
+                // Re-sending a short unchanged gap is cheaper than a new cursor move.
+                Some((_, end)) if col - *end < SET_POS_LEN => *end = col + 1,
+                _ => runs.push((col, col + 1)),
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+    use crate::peripheral::transport::MemoryTransport;
+
+    /// Full redraw cost of a 16-column panel.
+    const FULL: usize = ROWS * (SET_POS_LEN + 16);
+
+    fn flushed() -> (Framebuffer, Lcds<MemoryTransport>) {
+        let mut lcds = Lcds::new(MemoryTransport::new());
+        let mut framebuffer = Framebuffer::new(16);
+        framebuffer.write_str(0, 0, "Moisture 42%");
+        framebuffer.flush(&mut lcds).unwrap();
+        lcds.transport_mut().clear();
+        (framebuffer, lcds)
+    }
+
+    #[test]
+    fn first_flush_redraws_every_cell() {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        let mut framebuffer = Framebuffer::new(16);
+        framebuffer.write_str(0, 0, "Moisture 42%");
+        framebuffer.write_str(1, 4, "Temp 21C");
+
+        let stats = framebuffer.flush(&mut lcds).unwrap();
+        assert_eq!(stats, FlushStats { bytes_sent: FULL, bytes_saved: 0, runs: 2 });
+        assert_eq!(lcds.transport().screen(), ["Moisture 42%    ", "    Temp 21C    "]);
+    }
+
+    #[test]
+    fn stats_match_the_bytes_on_the_wire() {
+        let (mut framebuffer, mut lcds) = flushed();
+        framebuffer.write_str(0, 9, "57");
+
+        let stats = framebuffer.flush(&mut lcds).unwrap();
+        assert_eq!(stats, FlushStats { bytes_sent: SET_POS_LEN + 2, bytes_saved: FULL - SET_POS_LEN - 2, runs: 1 });
+        assert_eq!(lcds.transport().transfers().len(), 1);
+        assert_eq!(lcds.transport().bytes().len(), stats.bytes_sent);
+        assert_eq!(&lcds.transport().bytes()[SET_POS_LEN..], b"57");
+    }
+
+    #[test]
+    fn gaps_shorter_than_a_cursor_move_are_merged() {
+        let (mut framebuffer, mut lcds) = flushed();
+        // Six unchanged cells between the edits: cheaper to resend them.
+        framebuffer.put(0, 0, b'm');
+        framebuffer.put(0, 7, b'E');
+
+        let stats = framebuffer.flush(&mut lcds).unwrap();
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.bytes_sent, SET_POS_LEN + 8);
+        assert_eq!(&lcds.transport().bytes()[SET_POS_LEN..], b"moisturE");
+    }
+
+    #[test]
+    fn gaps_as_long_as_a_cursor_move_are_split() {
+        let (mut framebuffer, mut lcds) = flushed();
+        // Seven unchanged cells cost as much as a second `set_pos`.
+        framebuffer.put(0, 0, b'm');
+        framebuffer.put(0, 8, b'_');
+
+        let stats = framebuffer.flush(&mut lcds).unwrap();
+        assert_eq!(stats.runs, 2);
+        assert_eq!(stats.bytes_sent, 2 * (SET_POS_LEN + 1));
+        assert_eq!(lcds.transport().bytes().len(), stats.bytes_sent);
+    }
+
+    #[test]
+    fn unchanged_buffer_sends_nothing() {
+        let (mut framebuffer, mut lcds) = flushed();
+        let stats = framebuffer.flush(&mut lcds).unwrap();
+        assert_eq!(stats, FlushStats { bytes_sent: 0, bytes_saved: FULL, runs: 0 });
+        assert!(lcds.transport().bytes().is_empty());
+        assert_eq!(framebuffer.total_bytes_saved(), FULL as u64);
+    }
+
+    #[test]
+    fn invalidate_forces_a_full_redraw() {
+        let (mut framebuffer, mut lcds) = flushed();
+        framebuffer.invalidate();
+        let stats = framebuffer.flush(&mut lcds).unwrap();
+        assert_eq!(stats.bytes_sent, FULL);
+        assert_eq!(stats.runs, ROWS);
+    }
+}
@@ -0,0 +1,396 @@
+use std::error::Error;
+use std::fmt;
+
+use log::debug;
+
+use crate::peripheral::lcds::{Lcds, LcdsError};
+use crate::peripheral::transport::Transport;
+
+/// Width of a user-defined character in pixels.
+pub const GLYPH_WIDTH: usize = 5;
+
+/// Height of a user-defined character in pixels.
+pub const GLYPH_HEIGHT: usize = 8;
+
+/// Number of user character slots in the controller's RAM.
+pub const SLOTS: usize = 8;
+
+/// Errors reported while building or placing glyphs.
+#[derive(Debug)]
+pub enum GlyphError {
+    /// The image is larger than 5x8 pixels.
+    BadSize { width: usize, height: usize },
+    /// The ASCII art contains a character that is neither on nor off.
+    BadPixel(char),
+    /// The PBM data is malformed.
+    BadPbm(&'static str),
+    /// Every slot holds a glyph that is still in use this frame.
+    SlotsExhausted,
+    /// The display rejected or failed to receive a command.
+    Lcds(LcdsError),
+}
+
+impl fmt::Display for GlyphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlyphError::BadSize { width, height } => {
+                write!(f, "glyph is {}x{}, at most {}x{} is supported", width, height, GLYPH_WIDTH, GLYPH_HEIGHT)
+            }
+            GlyphError::BadPixel(c) => write!(f, "unexpected pixel character {:?}", c),
+            GlyphError::BadPbm(msg) => write!(f, "invalid PBM image: {}", msg),
+            GlyphError::SlotsExhausted => write!(f, "all {} user character slots are in use", SLOTS),
+            GlyphError::Lcds(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for GlyphError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GlyphError::Lcds(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LcdsError> for GlyphError {
+    fn from(e: LcdsError) -> Self {
+        GlyphError::Lcds(e)
+    }
+}
+
+/// A 5x8 user-defined character, one byte per row with the leftmost pixel in
+/// bit 4, as accepted by `define_user_char`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Glyph(pub [u8; GLYPH_HEIGHT]);
+
+impl Glyph {
+    /// Returns the row data to pass to `define_user_char`.
+    pub fn rows(&self) -> &[u8; GLYPH_HEIGHT] {
+        &self.0
+    }
+
+    /// Parses ASCII art of up to 8 lines of 5 pixels.
+    ///
+    /// `#`, `X`, `*`, `@` and `1` are lit pixels; `.`, `-`, `_`, `0` and
+    /// spaces are dark. Each line is read from its first character, so
+    /// leading spaces are dark pixels, not indentation. Empty lines before
+    /// and after the art are ignored, and smaller images are aligned to the
+    /// top left.
+    pub fn from_ascii_art(art: &str) -> Result<Self, GlyphError> {
+        let lines: Vec<&str> = art.lines().collect();
+        let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(0);
+        let last = lines.iter().rposition(|l| !l.is_empty()).map_or(0, |i| i + 1);
+        let lines = if first < last { &lines[first..last] } else { &[][..] };
+        // Trailing spaces are dark anyway; dropping them keeps the width check lenient.
+        let lines: Vec<&str> = lines.iter().map(|l| l.trim_end()).collect();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        if lines.len() > GLYPH_HEIGHT || width > GLYPH_WIDTH {
+            return Err(GlyphError::BadSize { width, height: lines.len() });
+        }
+
+        let mut rows = [0u8; GLYPH_HEIGHT];
+        for (row, line) in rows.iter_mut().zip(&lines) {
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '#' | 'X' | '*' | '@' | '1' => *row |= 1 << (GLYPH_WIDTH - 1 - x),
+                    '.' | '-' | '_' | '0' | ' ' => {}
+                    other => return Err(GlyphError::BadPixel(other)),
+                }
+            }
+        }
+        Ok(Glyph(rows))
+    }
+
+    /// Parses a plain (`P1`) or raw (`P4`) PBM bitmap of up to 5x8 pixels.
+    pub fn from_pbm(data: &[u8]) -> Result<Self, GlyphError> {
+        let mut pos = 0;
+        let magic = pbm_token(data, &mut pos).ok_or(GlyphError::BadPbm("missing magic number"))?;
+        let width = pbm_number(data, &mut pos)?;
+        let height = pbm_number(data, &mut pos)?;
+        if width > GLYPH_WIDTH || height > GLYPH_HEIGHT {
+            return Err(GlyphError::BadSize { width, height });
+        }
+
+        let mut rows = [0u8; GLYPH_HEIGHT];
+        match magic {
+            b"P1" => {
+                for row in rows.iter_mut().take(height) {
+                    for x in 0..width {
+                        let bit = pbm_bit(data, &mut pos)?;
+                        if bit {
+                            *row |= 1 << (GLYPH_WIDTH - 1 - x);
+                        }
+                    }
+                }
+            }
+            b"P4" => {
+                // A single whitespace byte separates the header from the raster.
+                pos += 1;
+                let raster = data.get(pos..pos + height).ok_or(GlyphError::BadPbm("raster too short"))?;
+                for (row, &byte) in rows.iter_mut().zip(raster) {
+                    // Rows are packed MSB first; keep the leftmost `width` pixels.
+                    let mask = !(0xFFu8 >> width);
+                    *row = (byte & mask) >> (8 - GLYPH_WIDTH);
+                }
+            }
+            _ => return Err(GlyphError::BadPbm("only P1 and P4 images are supported")),
+        }
+        Ok(Glyph(rows))
+    }
+}
+
+/// Returns the next whitespace-separated PBM header token, skipping comments.
+fn pbm_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if data.get(*pos) != Some(&b'#') {
+            break;
+        }
+        while *pos < data.len() && data[*pos] != b'\n' {
+            *pos += 1;
+        }
+    }
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() && data[*pos] != b'#' {
+        *pos += 1;
+    }
+    (*pos > start).then(|| &data[start..*pos])
+}
+
+fn pbm_number(data: &[u8], pos: &mut usize) -> Result<usize, GlyphError> {
+    pbm_token(data, pos)
+        .and_then(|t| std::str::from_utf8(t).ok())
+        .and_then(|t| t.parse().ok())
+        .ok_or(GlyphError::BadPbm("invalid image size"))
+}
+
+/// Reads one `0`/`1` pixel of a plain PBM raster; pixels need not be separated.
+fn pbm_bit(data: &[u8], pos: &mut usize) -> Result<bool, GlyphError> {
+    while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    let bit = match data.get(*pos) {
+        Some(b'0') => false,
+        Some(b'1') => true,
+        _ => return Err(GlyphError::BadPbm("raster too short")),
+    };
+    *pos += 1;
+    Ok(bit)
+}
+
+/// Water droplet, for moisture readings.
+pub const DROPLET: Glyph = Glyph([
+    0b00100, 0b00100, 0b01010, 0b01010, 0b10001, 0b10001, 0b01110, 0b00000,
+]);
+
+/// Sun, for light readings.
+pub const SUN: Glyph = Glyph([
+    0b00000, 0b10101, 0b01110, 0b11011, 0b01110, 0b10101, 0b00000, 0b00000,
+]);
+
+/// Thermometer, for temperature readings.
+pub const THERMOMETER: Glyph = Glyph([
+    0b00100, 0b01010, 0b01010, 0b01010, 0b01110, 0b11111, 0b11111, 0b01110,
+]);
+
+/// Leaf, for plant status.
+pub const LEAF: Glyph = Glyph([
+    0b00001, 0b00111, 0b01111, 0b11111, 0b11110, 0b01100, 0b10000, 0b00000,
+]);
+
+/// Battery, for supply status.
+pub const BATTERY: Glyph = Glyph([
+    0b01110, 0b10001, 0b10001, 0b10001, 0b11111, 0b11111, 0b11111, 0b11111,
+]);
+
+/// Wi-Fi signal, for network status.
+pub const WIFI: Glyph = Glyph([
+    0b00000, 0b01110, 0b10001, 0b00100, 0b01010, 0b00000, 0b00100, 0b00000,
+]);
+
+/// The built-in plant-themed icons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Icon {
+    Droplet,
+    Sun,
+    Thermometer,
+    Leaf,
+    Battery,
+    Wifi,
+}
+
+impl Icon {
+    /// Every built-in icon.
+    pub const ALL: [Icon; 6] = [Icon::Droplet, Icon::Sun, Icon::Thermometer, Icon::Leaf, Icon::Battery, Icon::Wifi];
+
+    /// Returns the bitmap for this icon.
+    pub fn glyph(self) -> Glyph {
+        match self {
+            Icon::Droplet => DROPLET,
+            Icon::Sun => SUN,
+            Icon::Thermometer => THERMOMETER,
+            Icon::Leaf => LEAF,
+            Icon::Battery => BATTERY,
+            Icon::Wifi => WIFI,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    glyph: Glyph,
+    last_used: u64,
+    frame: u64,
+}
+
+/// Tracks which glyph is loaded into each of the 8 user character slots.
+///
+/// Call `begin_frame` before drawing a screen. Glyphs requested during the
+/// frame stay loaded; when a new glyph needs a slot, an empty slot is used
+/// first, then the least recently used one not needed by the current frame.
+#[derive(Debug, Clone, Default)]
+pub struct GlyphCache {
+    slots: [Option<Slot>; SLOTS],
+    clock: u64,
+    frame: u64,
+}
+
+impl GlyphCache {
+    /// Creates a cache that assumes no glyphs are loaded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new frame; glyphs from earlier frames may now be replaced.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Forgets every loaded glyph, e.g. after the display was reset.
+    pub fn invalidate(&mut self) {
+        self.slots = [None; SLOTS];
+    }
+
+    /// Returns the slot holding `glyph`, if it is loaded.
+    pub fn slot_of(&self, glyph: &Glyph) -> Option<u8> {
+        self.slots
+            .iter()
+            .position(|s| s.is_some_and(|s| s.glyph == *glyph))
+            .map(|i| i as u8)
+    }
+
+    /// Returns the glyph loaded in each slot.
+    pub fn loaded(&self) -> [Option<Glyph>; SLOTS] {
+        self.slots.map(|s| s.map(|s| s.glyph))
+    }
+
+    /// Returns true if every glyph in `glyphs` can be loaded in the current
+    /// frame without evicting a glyph this frame already uses.
+    pub fn can_fit(&self, glyphs: &[Glyph]) -> bool {
+        let mut distinct: Vec<&Glyph> = glyphs.iter().collect();
+        distinct.sort_by_key(|g| g.0);
+        distinct.dedup();
+        let needed = distinct
+            .iter()
+            .filter(|g| {
+                !self
+                    .slots
+                    .iter()
+                    .any(|s| s.is_some_and(|s| s.glyph == ***g && s.frame == self.frame))
+            })
+            .count();
+        let available = self
+            .slots
+            .iter()
+            .filter(|s| s.is_none_or(|s| s.frame != self.frame))
+            .count();
+        needed <= available
+    }
+
+    /// Makes sure `glyph` is loaded and returns its slot.
+    ///
+    /// # Errors
+    /// * Returns `SlotsExhausted` if all slots are needed by this frame, or
+    ///   the driver error if defining the character fails.
+    pub fn load<T: Transport>(&mut self, lcds: &mut Lcds<T>, glyph: &Glyph) -> Result<u8, GlyphError> {
+        self.clock += 1;
+        if let Some(slot) = self.slot_of(glyph) {
+            let entry = self.slots[slot as usize].as_mut().unwrap();
+            entry.last_used = self.clock;
+            entry.frame = self.frame;
+            return Ok(slot);
+        }
+
+        let frame = self.frame;
+        let slot = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .or_else(|| {
+                self.slots
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, s)| s.filter(|s| s.frame != frame).map(|s| (i, s.last_used)))
+                    .min_by_key(|&(_, used)| used)
+                    .map(|(i, _)| i)
+            })
+            .ok_or(GlyphError::SlotsExhausted)?;
+
+        // Forget the slot first so a failed write never leaves a stale mapping.
+        self.slots[slot] = None;
+        lcds.define_user_char(glyph.rows(), slot as u8)?;
+        debug!("loaded glyph {:?} into slot {}", glyph, slot);
+        self.slots[slot] = Some(Slot { glyph: *glyph, last_used: self.clock, frame });
+        Ok(slot as u8)
+    }
+
+    /// Loads `glyph` if needed and shows it at the given position.
+    pub fn show<T: Transport>(&mut self, lcds: &mut Lcds<T>, glyph: &Glyph, row: u8, col: u8) -> Result<u8, GlyphError> {
+        let slot = self.load(lcds, glyph)?;
+        lcds.disp_user_char(&[slot], 1, row, col)?;
+        Ok(slot)
+    }
+
+    /// Loads a built-in icon if needed and shows it at the given position.
+    pub fn show_icon<T: Transport>(&mut self, lcds: &mut Lcds<T>, icon: Icon, row: u8, col: u8) -> Result<u8, GlyphError> {
+        self.show(lcds, &icon.glyph(), row, col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_art_keeps_leading_dark_pixels() {
+        let glyph = Glyph::from_ascii_art("  #  \n  #  \n ### ").unwrap();
+        assert_eq!(glyph.rows()[..3], [0b00100, 0b00100, 0b01110]);
+        assert_eq!(glyph.rows()[3..], [0; 5]);
+    }
+
+    #[test]
+    fn ascii_art_skips_empty_edge_lines() {
+        let glyph = Glyph::from_ascii_art("\n     \n#...#\n").unwrap();
+        assert_eq!(glyph.rows()[..2], [0, 0b10001]);
+    }
+
+    #[test]
+    fn ascii_art_rejects_unknown_and_oversized_input() {
+        assert!(matches!(Glyph::from_ascii_art("\u{e9}#"), Err(GlyphError::BadPixel('\u{e9}'))));
+        assert!(matches!(
+            Glyph::from_ascii_art("    ##"),
+            Err(GlyphError::BadSize { width: 6, height: 1 })
+        ));
+    }
+
+    #[test]
+    fn plain_and_raw_pbm_agree() {
+        let plain = Glyph::from_pbm(b"P1\n# arrow\n5 3\n00100 01110 11111").unwrap();
+        let raw = Glyph::from_pbm(&[b'P', b'4', b' ', b'5', b' ', b'3', b'\n', 0x20, 0x70, 0xF8]).unwrap();
+        assert_eq!(plain, raw);
+        assert_eq!(plain.rows()[..3], [0b00100, 0b01110, 0b11111]);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+
+use super::glyph::{GLYPH_HEIGHT, GLYPH_WIDTH, Glyph, GlyphCache, GlyphError};
+use crate::peripheral::lcds::Lcds;
+use crate::peripheral::transport::Transport;
+
+/// Character ROM code of the solid block, available without using a slot.
+pub const FULL_BLOCK: u8 = 0xFF;
+
+/// Character ROM code of an empty cell.
+pub const EMPTY: u8 = b' ';
+
+/// Returns a glyph with the leftmost `cols` pixel columns lit.
+fn partial_bar(cols: usize) -> Glyph {
+    let mask = (0x1Fu8 << (GLYPH_WIDTH - cols)) & 0x1F;
+    Glyph([mask; GLYPH_HEIGHT])
+}
+
+/// Returns a glyph with the bottom `rows` pixel rows lit.
+fn column(rows: usize) -> Glyph {
+    let mut glyph = [0u8; GLYPH_HEIGHT];
+    glyph[GLYPH_HEIGHT - rows..].fill(0x1F);
+    Glyph(glyph)
+}
+
+/// Maps `value` within `min..=max` to `0..=steps`.
+fn scale(value: f32, min: f32, max: f32, steps: usize) -> usize {
+    if max <= min || value.is_nan() {
+        return 0;
+    }
+    let ratio = ((value - min) / (max - min)).clamp(0.0, 1.0);
+    (ratio * steps as f32).round() as usize
+}
+
+/// A horizontal bar with one-pixel-column resolution.
+///
+/// Full cells use the ROM block character, so a bar needs at most one user
+/// character slot for its partially filled cell. Bars of the same length
+/// remainder share that slot through the [`GlyphCache`].
+#[derive(Debug, Clone)]
+pub struct BarGraph {
+    width: usize,
+    min: f32,
+    max: f32,
+}
+
+impl BarGraph {
+    /// Creates a bar `width` cells long covering `min..=max`.
+    pub fn new(width: usize, min: f32, max: f32) -> Self {
+        Self { width: width.max(1), min, max }
+    }
+
+    /// Returns the bar length in pixel columns for `value`.
+    pub fn pixels(&self, value: f32) -> usize {
+        scale(value, self.min, self.max, self.width * GLYPH_WIDTH)
+    }
+
+    /// Returns the character codes for `value`, loading the partial glyph if needed.
+    pub fn render<T: Transport>(
+        &self,
+        cache: &mut GlyphCache,
+        lcds: &mut Lcds<T>,
+        value: f32,
+    ) -> Result<Vec<u8>, GlyphError> {
+        let pixels = self.pixels(value);
+        let full = pixels / GLYPH_WIDTH;
+        let rest = pixels % GLYPH_WIDTH;
+        let mut cells = vec![FULL_BLOCK; full];
+        if rest > 0 {
+            cells.push(cache.load(lcds, &partial_bar(rest))?);
+        }
+        cells.resize(self.width, EMPTY);
+        Ok(cells)
+    }
+
+    /// Renders the bar for `value` and writes it at the given position.
+    pub fn draw<T: Transport>(
+        &self,
+        cache: &mut GlyphCache,
+        lcds: &mut Lcds<T>,
+        value: f32,
+        row: u8,
+        col: u8,
+    ) -> Result<(), GlyphError> {
+        let cells = self.render(cache, lcds, value)?;
+        lcds.set_pos(row, col)?;
+        lcds.write_data(&cells)?;
+        Ok(())
+    }
+}
+
+/// A one-row chart of the most recent readings, one cell per reading.
+///
+/// Each cell is a column up to 8 pixels tall. Heights 1-7 need a user
+/// character each; when the slots left in the current frame cannot hold
+/// every height shown, the sparkline falls back to fewer, coarser levels
+/// rather than failing.
+#[derive(Debug, Clone)]
+pub struct Sparkline {
+    width: usize,
+    range: Option<(f32, f32)>,
+    history: VecDeque<f32>,
+}
+
+impl Sparkline {
+    /// Creates a sparkline holding the last `width` readings, auto-scaled to
+    /// the smallest and largest reading shown.
+    pub fn new(width: usize) -> Self {
+        Self {
+            width: width.max(1),
+            range: None,
+            history: VecDeque::with_capacity(width),
+        }
+    }
+
+    /// Uses a fixed `min..=max` scale instead of auto-scaling.
+    pub fn with_range(mut self, min: f32, max: f32) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    /// Appends a reading, dropping the oldest once the sparkline is full.
+    pub fn push(&mut self, value: f32) {
+        if self.history.len() == self.width {
+            self.history.pop_front();
+        }
+        self.history.push_back(value);
+    }
+
+    /// Returns the readings shown, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = f32> + '_ {
+        self.history.iter().copied()
+    }
+
+    /// Returns the column height (0-8) of each reading at the given resolution.
+    fn heights(&self, levels: usize) -> Vec<usize> {
+        let (min, max) = self.range.unwrap_or_else(|| {
+            self.history
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)))
+        });
+        self.history
+            .iter()
+            .map(|&v| {
+                // Auto-scaled flat data sits at the bottom rather than vanishing.
+                let level = if max > min { scale(v, min, max, levels) } else { 1.min(levels) };
+                level * GLYPH_HEIGHT / levels
+            })
+            .collect()
+    }
+
+    /// Returns the character codes for the current history, right-aligned.
+    pub fn render<T: Transport>(&self, cache: &mut GlyphCache, lcds: &mut Lcds<T>) -> Result<Vec<u8>, GlyphError> {
+        // Halve the resolution until the partial columns fit the free slots.
+        let mut levels = GLYPH_HEIGHT;
+        let heights = loop {
+            let heights = self.heights(levels);
+            let glyphs: Vec<Glyph> = heights
+                .iter()
+                .filter(|&&h| h > 0 && h < GLYPH_HEIGHT)
+                .map(|&h| column(h))
+                .collect();
+            if levels == 1 || cache.can_fit(&glyphs) {
+                break heights;
+            }
+            levels /= 2;
+        };
+
+        let mut cells = vec![EMPTY; self.width - self.history.len()];
+        for height in heights {
+            cells.push(match height {
+                0 => EMPTY,
+                GLYPH_HEIGHT => FULL_BLOCK,
+                h => cache.load(lcds, &column(h))?,
+            });
+        }
+        Ok(cells)
+    }
+
+    /// Renders the sparkline and writes it at the given position.
+    pub fn draw<T: Transport>(
+        &self,
+        cache: &mut GlyphCache,
+        lcds: &mut Lcds<T>,
+        row: u8,
+        col: u8,
+    ) -> Result<(), GlyphError> {
+        let cells = self.render(cache, lcds)?;
+        lcds.set_pos(row, col)?;
+        lcds.write_data(&cells)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::glyph::{Icon, SLOTS};
+    use crate::peripheral::emulator::LcdsEmulator;
+
+    fn setup() -> (GlyphCache, Lcds<LcdsEmulator>) {
+        (GlyphCache::new(), Lcds::new(LcdsEmulator::new_2x16()))
+    }
+
+    /// Fills `count` slots with glyphs the current frame uses.
+    fn occupy(cache: &mut GlyphCache, lcds: &mut Lcds<LcdsEmulator>, count: usize) {
+        let fillers = Icon::ALL.iter().map(|icon| icon.glyph()).chain([partial_bar(1), partial_bar(3)]);
+        for glyph in fillers.take(count) {
+            cache.load(lcds, &glyph).unwrap();
+        }
+    }
+
+    fn used_slots(cache: &GlyphCache) -> usize {
+        cache.loaded().iter().flatten().count()
+    }
+
+    #[test]
+    fn bar_uses_rom_blocks_for_whole_cells() {
+        let (mut cache, mut lcds) = setup();
+        let bar = BarGraph::new(4, 0.0, 100.0);
+        assert_eq!(bar.render(&mut cache, &mut lcds, 50.0).unwrap(), [FULL_BLOCK, FULL_BLOCK, EMPTY, EMPTY]);
+        assert_eq!(bar.render(&mut cache, &mut lcds, 250.0).unwrap(), [FULL_BLOCK; 4]);
+        assert_eq!(bar.render(&mut cache, &mut lcds, f32::NAN).unwrap(), [EMPTY; 4]);
+        assert_eq!(used_slots(&cache), 0);
+    }
+
+    #[test]
+    fn bar_partial_cell_lights_the_leftmost_columns() {
+        let (mut cache, mut lcds) = setup();
+        let bar = BarGraph::new(4, 0.0, 100.0);
+        // 60% of 20 pixel columns: two full cells and two columns of the third.
+        assert_eq!(bar.pixels(60.0), 12);
+        assert_eq!(bar.render(&mut cache, &mut lcds, 60.0).unwrap(), [FULL_BLOCK, FULL_BLOCK, 0, EMPTY]);
+        assert_eq!(lcds.transport().defined_char(0), [0b11000; 8]);
+    }
+
+    #[test]
+    fn bars_with_the_same_remainder_share_a_slot() {
+        let (mut cache, mut lcds) = setup();
+        let bar = BarGraph::new(4, 0.0, 100.0);
+        bar.draw(&mut cache, &mut lcds, 35.0, 0, 0).unwrap();
+        bar.draw(&mut cache, &mut lcds, 60.0, 1, 0).unwrap();
+        assert_eq!(used_slots(&cache), 1);
+        assert_eq!(lcds.transport().cell(0, 1), 0);
+        assert_eq!(lcds.transport().cell(1, 2), 0);
+    }
+
+    #[test]
+    fn sparkline_is_right_aligned_and_drops_old_readings() {
+        let (mut cache, mut lcds) = setup();
+        let mut sparkline = Sparkline::new(4).with_range(0.0, 8.0);
+        sparkline.push(8.0);
+        sparkline.push(0.0);
+        assert_eq!(sparkline.render(&mut cache, &mut lcds).unwrap(), [EMPTY, EMPTY, FULL_BLOCK, EMPTY]);
+        for value in [8.0, 8.0, 8.0] {
+            sparkline.push(value);
+        }
+        assert_eq!(sparkline.history().collect::<Vec<_>>(), [0.0, 8.0, 8.0, 8.0]);
+    }
+
+    #[test]
+    fn sparkline_uses_every_height_when_slots_allow() {
+        let (mut cache, mut lcds) = setup();
+        let mut sparkline = Sparkline::new(7).with_range(0.0, 8.0);
+        for value in 1..=7 {
+            sparkline.push(value as f32);
+        }
+        let cells = sparkline.render(&mut cache, &mut lcds).unwrap();
+        assert_eq!(used_slots(&cache), 7);
+        for (height, &code) in (1..=7).zip(&cells) {
+            assert_eq!(cache.loaded()[code as usize], Some(column(height)));
+        }
+    }
+
+    #[test]
+    fn sparkline_halves_its_levels_when_slots_run_short() {
+        let (mut cache, mut lcds) = setup();
+        occupy(&mut cache, &mut lcds, SLOTS - 4);
+        let mut sparkline = Sparkline::new(7).with_range(0.0, 8.0);
+        for value in 1..=7 {
+            sparkline.push(value as f32);
+        }
+        let cells = sparkline.render(&mut cache, &mut lcds).unwrap();
+        // Four levels: heights 2, 4 and 6 need a slot each, 8 is the ROM block.
+        let heights: Vec<_> = cells
+            .iter()
+            .map(|&code| match code {
+                FULL_BLOCK => GLYPH_HEIGHT,
+                slot => cache.loaded()[slot as usize].unwrap().rows().iter().filter(|&&r| r != 0).count(),
+            })
+            .collect();
+        assert_eq!(heights, [2, 2, 4, 4, 6, 6, 8]);
+        assert_eq!(used_slots(&cache), SLOTS - 1);
+    }
+
+    #[test]
+    fn sparkline_falls_back_to_on_off_without_free_slots() {
+        let (mut cache, mut lcds) = setup();
+        occupy(&mut cache, &mut lcds, SLOTS);
+        let before = cache.loaded();
+        let mut sparkline = Sparkline::new(4).with_range(0.0, 8.0);
+        for value in [1.0, 3.0, 5.0, 7.0] {
+            sparkline.push(value);
+        }
+        assert_eq!(sparkline.render(&mut cache, &mut lcds).unwrap(), [EMPTY, EMPTY, FULL_BLOCK, FULL_BLOCK]);
+        assert_eq!(cache.loaded(), before);
+    }
+}
@@ -0,0 +1,308 @@
+use std::time::{Duration, Instant};
+
+use super::framebuffer::MAX_COLS;
+use crate::peripheral::charset::Charset;
+use crate::peripheral::lcds::{Lcds, Result};
+use crate::peripheral::transport::Transport;
+
+/// Default time between single-column steps.
+pub const DEFAULT_SPEED: Duration = Duration::from_millis(300);
+
+/// Default time the text rests at either end.
+pub const DEFAULT_PAUSE: Duration = Duration::from_millis(1500);
+
+/// Blank columns inserted between the end and the start of wrapping text.
+const WRAP_GAP: usize = 3;
+
+/// How the text moves once it reaches its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollMode {
+    /// Keep scrolling left, starting over after a short gap.
+    Wrap,
+    /// Reverse direction at each end.
+    Bounce,
+}
+
+/// A scrolling text field for strings longer than the space on a row.
+///
+/// The display's own scroll command shifts both rows at once, so the marquee
+/// moves its text within a window of one row and redraws that window with a
+/// cursor move plus the visible characters. Text that fits is drawn once and
+/// left alone. Text is encoded for the character ROM up front, so widths
+/// and offsets are counted in display cells.
+#[derive(Debug, Clone)]
+pub struct Marquee {
+    source: String,
+    charset: Charset,
+    text: Vec<u8>,
+    row: u8,
+    col: u8,
+    width: usize,
+    speed: Duration,
+    pause: Duration,
+    mode: ScrollMode,
+    offset: usize,
+    forward: bool,
+    next_step: Option<Instant>,
+    dirty: bool,
+}
+
+impl Marquee {
+    /// Creates a marquee spanning `width` columns of `row`, starting at column 0.
+    /// The width is limited to the 40 columns of a row.
+    pub fn new(row: u8, width: usize, text: &str) -> Self {
+        let charset = Charset::new();
+        Self {
+            source: text.to_string(),
+            text: charset.encode_lossy(text),
+            charset,
+            row,
+            col: 0,
+            width: width.clamp(1, MAX_COLS),
+            speed: DEFAULT_SPEED,
+            pause: DEFAULT_PAUSE,
+            mode: ScrollMode::Wrap,
+            offset: 0,
+            forward: true,
+            next_step: None,
+            dirty: true,
+        }
+    }
+
+    /// Sets the column the window starts at. The width is narrowed if the
+    /// window would otherwise run past the end of the row.
+    pub fn with_col(mut self, col: u8) -> Self {
+        self.col = col;
+        self.width = self.width.min(MAX_COLS.saturating_sub(col as usize)).max(1);
+        self
+    }
+
+    /// Sets the time between single-column steps.
+    pub fn with_speed(mut self, speed: Duration) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Sets how long the text rests at either end.
+    pub fn with_pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Sets the charset the text is encoded with, e.g. the display driver's.
+    pub fn with_charset(mut self, charset: Charset) -> Self {
+        self.text = charset.encode_lossy(&self.source);
+        self.charset = charset;
+        self
+    }
+
+    /// Sets wrap or bounce behavior.
+    pub fn with_mode(mut self, mode: ScrollMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Replaces the text and restarts from the beginning.
+    pub fn set_text(&mut self, text: &str) {
+        self.source = text.to_string();
+        self.text = self.charset.encode_lossy(text);
+        self.offset = 0;
+        self.forward = true;
+        self.next_step = None;
+        self.dirty = true;
+    }
+
+    /// Returns true if the text is longer than the window.
+    pub fn scrolls(&self) -> bool {
+        self.text.len() > self.width
+    }
+
+    /// Returns the characters currently inside the window, padded with spaces.
+    pub fn window(&self) -> Vec<u8> {
+        if !self.scrolls() {
+            let mut out = self.text.clone();
+            out.resize(self.width, b' ');
+            return out;
+        }
+        match self.mode {
+            ScrollMode::Bounce => self.text[self.offset..self.offset + self.width].to_vec(),
+            ScrollMode::Wrap => {
+                let cycle = self.text.len() + WRAP_GAP;
+                (0..self.width)
+                    .map(|i| *self.text.get((self.offset + i) % cycle).unwrap_or(&b' '))
+                    .collect()
+            }
+        }
+    }
+
+    /// Advances the text if its step is due. Returns true if the window changed.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if !self.scrolls() {
+            return std::mem::take(&mut self.dirty);
+        }
+        let due = *self.next_step.get_or_insert(now + self.pause);
+        if now < due {
+            return std::mem::take(&mut self.dirty);
+        }
+        let at_end = self.step();
+        self.next_step = Some(now + if at_end { self.pause } else { self.speed });
+        self.dirty = false;
+        true
+    }
+
+    /// Writes the current window to the display.
+    pub fn draw<T: Transport>(&self, lcds: &mut Lcds<T>) -> Result<()> {
+        lcds.batch(|lcds| {
+            lcds.set_pos(self.row, self.col)?;
+            lcds.write_data(&self.window())
+        })
+    }
+
+    /// Advances the text if due and redraws it when the window changed.
+    /// Returns true if anything was written.
+    pub fn update<T: Transport>(&mut self, lcds: &mut Lcds<T>, now: Instant) -> Result<bool> {
+        if !self.tick(now) {
+            return Ok(false);
+        }
+        self.draw(lcds)?;
+        Ok(true)
+    }
+
+    /// Moves one column and returns true if the text came to rest at an end.
+    fn step(&mut self) -> bool {
+        match self.mode {
+            ScrollMode::Wrap => {
+                self.offset = (self.offset + 1) % (self.text.len() + WRAP_GAP);
+                self.offset == 0
+            }
+            ScrollMode::Bounce => {
+                let max = self.text.len() - self.width;
+                if self.forward {
+                    self.offset += 1;
+                } else {
+                    self.offset -= 1;
+                }
+                let at_end = self.offset == 0 || self.offset == max;
+                if at_end {
+                    self.forward = self.offset == 0;
+                }
+                at_end
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+
+    const SPEED: Duration = Duration::from_millis(100);
+    const PAUSE: Duration = Duration::from_secs(1);
+
+    fn marquee(mode: ScrollMode) -> Marquee {
+        Marquee::new(0, 4, "ABCDEF").with_speed(SPEED).with_pause(PAUSE).with_mode(mode)
+    }
+
+    fn window(marquee: &Marquee) -> String {
+        String::from_utf8(marquee.window()).unwrap()
+    }
+
+    #[test]
+    fn text_that_fits_is_drawn_once() {
+        let mut marquee = Marquee::new(0, 8, "Hi");
+        let now = Instant::now();
+        assert!(!marquee.scrolls());
+        assert_eq!(window(&marquee), "Hi      ");
+        assert!(marquee.tick(now));
+        assert!(!marquee.tick(now + PAUSE * 10));
+    }
+
+    #[test]
+    fn rests_before_the_first_step() {
+        let mut marquee = marquee(ScrollMode::Wrap);
+        let start = Instant::now();
+        // The first tick only reports the initial draw.
+        assert!(marquee.tick(start));
+        assert!(!marquee.tick(start + PAUSE / 2));
+        assert_eq!(window(&marquee), "ABCD");
+        assert!(marquee.tick(start + PAUSE));
+        assert_eq!(window(&marquee), "BCDE");
+        assert!(!marquee.tick(start + PAUSE + SPEED / 2));
+    }
+
+    #[test]
+    fn wrap_runs_through_the_gap_and_pauses_at_the_start() {
+        let mut marquee = marquee(ScrollMode::Wrap);
+        let mut now = Instant::now();
+        marquee.tick(now);
+        now += PAUSE;
+        let mut seen = Vec::new();
+        // Six characters plus the three-column gap make a nine-step cycle.
+        for _ in 0..9 {
+            assert!(marquee.tick(now));
+            seen.push(window(&marquee));
+            now += SPEED;
+        }
+        assert_eq!(seen, ["BCDE", "CDEF", "DEF ", "EF  ", "F   ", "   A", "  AB", " ABC", "ABCD"]);
+        // Back at the start, so the next step waits for the pause.
+        assert!(!marquee.tick(now));
+        assert!(marquee.tick(now - SPEED + PAUSE));
+    }
+
+    #[test]
+    fn bounce_reverses_and_pauses_at_both_ends() {
+        let mut marquee = marquee(ScrollMode::Bounce);
+        let mut now = Instant::now();
+        marquee.tick(now);
+        now += PAUSE;
+        assert!(marquee.tick(now));
+        assert_eq!(window(&marquee), "BCDE");
+        now += SPEED;
+        assert!(marquee.tick(now));
+        assert_eq!(window(&marquee), "CDEF");
+        // At the right end: a speed step is too early.
+        assert!(!marquee.tick(now + SPEED));
+        now += PAUSE;
+        assert!(marquee.tick(now));
+        assert_eq!(window(&marquee), "BCDE");
+        now += SPEED;
+        assert!(marquee.tick(now));
+        assert_eq!(window(&marquee), "ABCD");
+        assert!(!marquee.tick(now + SPEED));
+        assert!(marquee.tick(now + PAUSE));
+        assert_eq!(window(&marquee), "BCDE");
+    }
+
+    #[test]
+    fn set_text_restarts_from_the_beginning() {
+        let mut marquee = marquee(ScrollMode::Bounce);
+        let now = Instant::now();
+        marquee.tick(now);
+        marquee.tick(now + PAUSE);
+        marquee.set_text("UVWXYZ");
+        assert_eq!(window(&marquee), "UVWX");
+        assert!(marquee.tick(now + PAUSE));
+        assert!(!marquee.tick(now + PAUSE + SPEED));
+    }
+
+    #[test]
+    fn width_is_limited_to_the_row() {
+        assert_eq!(Marquee::new(0, 100, "x").window().len(), MAX_COLS);
+        assert_eq!(Marquee::new(0, 0, "x").window().len(), 1);
+        assert_eq!(Marquee::new(0, 16, "x").with_col(30).window().len(), 10);
+        assert_eq!(Marquee::new(0, 16, "x").with_col(45).window().len(), 1);
+    }
+
+    #[test]
+    fn update_draws_the_window_in_place() {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        let mut marquee = marquee(ScrollMode::Wrap).with_col(3);
+        let now = Instant::now();
+        assert!(marquee.update(&mut lcds, now).unwrap());
+        assert_eq!(lcds.transport().line(0), "   ABCD         ");
+        assert!(!marquee.update(&mut lcds, now).unwrap());
+        assert!(marquee.update(&mut lcds, now + PAUSE).unwrap());
+        assert_eq!(lcds.transport().line(0), "   BCDE         ");
+    }
+}
@@ -0,0 +1,460 @@
+use log::debug;
+
+use crate::peripheral::input::InputEvent;
+use crate::peripheral::lcds::{Lcds, Result};
+use crate::peripheral::transport::Transport;
+
+/// Runs a menu action and returns a short status line to show.
+pub type ActionFn = Box<dyn FnMut() -> String>;
+
+/// Produces the text shown for a read-only value.
+pub type InfoFn = Box<dyn Fn() -> String>;
+
+/// An adjustable integer setting, such as a moisture threshold.
+pub struct Setting {
+    pub get: Box<dyn Fn() -> i32>,
+    pub set: Box<dyn FnMut(i32)>,
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+    pub unit: String,
+}
+
+/// What selecting a menu item does.
+pub enum MenuAction {
+    /// Runs a command, e.g. manual watering.
+    Run(ActionFn),
+    /// Shows a read-only value on the second row, e.g. a calibration constant.
+    Info(InfoFn),
+    /// Edits a value with Up/Down (reversed for an encoder, see
+    /// `MenuSystem::with_encoder`); Select saves and Back cancels.
+    Edit(Setting),
+    /// Opens a nested menu.
+    Submenu(Menu),
+}
+
+/// A labelled menu entry.
+pub struct MenuItem {
+    pub label: String,
+    pub action: MenuAction,
+}
+
+/// An ordered list of menu items.
+#[derive(Default)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+}
+
+impl Menu {
+    /// Creates an empty menu.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an item that runs `run` when selected.
+    pub fn action<F: FnMut() -> String + 'static>(mut self, label: &str, run: F) -> Self {
+        self.push(label, MenuAction::Run(Box::new(run)));
+        self
+    }
+
+    /// Adds an item that shows `value` on the second row.
+    pub fn info<F: Fn() -> String + 'static>(mut self, label: &str, value: F) -> Self {
+        self.push(label, MenuAction::Info(Box::new(value)));
+        self
+    }
+
+    /// Adds an editable setting.
+    pub fn setting(mut self, label: &str, setting: Setting) -> Self {
+        self.push(label, MenuAction::Edit(setting));
+        self
+    }
+
+    /// Adds a nested menu.
+    pub fn submenu(mut self, label: &str, menu: Menu) -> Self {
+        self.push(label, MenuAction::Submenu(menu));
+        self
+    }
+
+    fn push(&mut self, label: &str, action: MenuAction) {
+        self.items.push(MenuItem {
+            label: label.to_string(),
+            action,
+        });
+    }
+
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the menu has no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Drives a [`Menu`] tree from input events and renders it on two rows.
+///
+/// Row 0 shows the selected item behind a `>` marker. Row 1 shows its value,
+/// the result of the last action, or the next item. While a setting is being
+/// edited the cursor blinks on its value. Back (or a long Select) leaves a
+/// submenu, and closes the menu at the top level.
+pub struct MenuSystem {
+    root: Menu,
+    // Selected index at each level; the last entry is the current menu.
+    path: Vec<usize>,
+    editing: Option<i32>,
+    message: Option<String>,
+    open: bool,
+    encoder: bool,
+}
+
+impl MenuSystem {
+    /// Creates a closed menu system over `root`.
+    pub fn new(root: Menu) -> Self {
+        Self {
+            root,
+            path: vec![0],
+            editing: None,
+            message: None,
+            open: false,
+            encoder: false,
+        }
+    }
+
+    /// Marks the input as a rotary encoder. An encoder reports clockwise as
+    /// `Down` so lists move forward; with this set, clockwise also raises a
+    /// value while editing instead of lowering it as the Down button does.
+    pub fn with_encoder(mut self, encoder: bool) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Returns true while the menu is shown.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the menu at the top level.
+    pub fn open(&mut self) {
+        self.path = vec![0];
+        self.editing = None;
+        self.message = None;
+        self.open = true;
+    }
+
+    /// Closes the menu.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.editing = None;
+    }
+
+    /// Returns true while a setting is being edited.
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Returns the label of the selected item.
+    pub fn selected_label(&self) -> Option<&str> {
+        let menu = self.current_menu();
+        menu.items.get(self.selected()).map(|i| i.label.as_str())
+    }
+
+    fn selected(&self) -> usize {
+        *self.path.last().unwrap()
+    }
+
+    fn current_menu(&self) -> &Menu {
+        let mut menu = &self.root;
+        for &index in &self.path[..self.path.len() - 1] {
+            let MenuAction::Submenu(sub) = &menu.items[index].action else {
+                unreachable!("menu path only descends into submenus");
+            };
+            menu = sub;
+        }
+        menu
+    }
+
+    fn current_menu_mut(&mut self) -> &mut Menu {
+        let mut menu = &mut self.root;
+        for &index in &self.path[..self.path.len() - 1] {
+            let MenuAction::Submenu(sub) = &mut menu.items[index].action else {
+                unreachable!("menu path only descends into submenus");
+            };
+            menu = sub;
+        }
+        menu
+    }
+
+    /// Applies an input event. Returns true if the screen needs redrawing.
+    pub fn handle(&mut self, event: InputEvent) -> bool {
+        if !self.open {
+            return false;
+        }
+        if self.editing.is_some() {
+            self.handle_edit(event);
+            return true;
+        }
+
+        let len = self.current_menu().len();
+        if len == 0 && !matches!(event, InputEvent::Back | InputEvent::LongSelect) {
+            return false;
+        }
+        self.message = None;
+        let selected = self.selected();
+        match event {
+            InputEvent::Up => *self.path.last_mut().unwrap() = (selected + len - 1) % len,
+            InputEvent::Down => *self.path.last_mut().unwrap() = (selected + 1) % len,
+            InputEvent::Select => self.select(),
+            InputEvent::Back | InputEvent::LongSelect => {
+                if self.path.len() > 1 {
+                    self.path.pop();
+                } else {
+                    self.close();
+                }
+            }
+        }
+        true
+    }
+
+    fn select(&mut self) {
+        let selected = self.selected();
+        let item = &mut self.current_menu_mut().items[selected];
+        debug!("menu select {}", item.label);
+        match &mut item.action {
+            MenuAction::Run(run) => {
+                let message = run();
+                self.message = Some(message);
+            }
+            MenuAction::Info(_) => {}
+            MenuAction::Edit(setting) => {
+                let value = (setting.get)();
+                self.editing = Some(value);
+            }
+            MenuAction::Submenu(_) => self.path.push(0),
+        }
+    }
+
+    fn handle_edit(&mut self, event: InputEvent) {
+        let encoder = self.encoder;
+        let selected = self.selected();
+        let value = self.editing.unwrap();
+        let MenuAction::Edit(setting) = &mut self.current_menu_mut().items[selected].action else {
+            self.editing = None;
+            return;
+        };
+        match event {
+            // Up raises the value, except on an encoder where clockwise (Down) does.
+            InputEvent::Up | InputEvent::Down if (event == InputEvent::Up) != encoder => {
+                self.editing = Some(value.saturating_add(setting.step).min(setting.max));
+            }
+            InputEvent::Up | InputEvent::Down => {
+                self.editing = Some(value.saturating_sub(setting.step).max(setting.min));
+            }
+            InputEvent::Select => {
+                (setting.set)(value);
+                self.editing = None;
+                self.message = Some("Saved".to_string());
+            }
+            InputEvent::Back | InputEvent::LongSelect => self.editing = None,
+        }
+    }
+
+    /// Draws the menu, or does nothing while it is closed.
+    pub fn render<T: Transport>(&self, lcds: &mut Lcds<T>) -> Result<()> {
+        if !self.open {
+            return Ok(());
+        }
+        lcds.batch(|lcds| self.draw(lcds))
+    }
+
+    fn draw<T: Transport>(&self, lcds: &mut Lcds<T>) -> Result<()> {
+        let menu = self.current_menu();
+        let selected = self.selected();
+        let Some(item) = menu.items.get(selected) else {
+            lcds.cursor_mode_set(false, false)?;
+            lcds.display_clear()?;
+            return lcds.write_string_at_pos(0, 0, "(empty)");
+        };
+
+        let marker = if matches!(item.action, MenuAction::Submenu(_)) { "+" } else { ">" };
+        Self::write_line(lcds, 0, &format!("{}{}", marker, item.label))?;
+
+        let (detail, edit_col) = match (&item.action, self.editing) {
+            (MenuAction::Edit(setting), Some(value)) => {
+                let text = format!(" {}{}", value, setting.unit);
+                let col = lcds.charset().width(&text) - lcds.charset().width(&setting.unit) - 1;
+                (text, Some(col))
+            }
+            _ => (self.detail(menu, selected), None),
+        };
+        Self::write_line(lcds, 1, &detail)?;
+
+        match edit_col {
+            Some(col) => {
+                lcds.set_pos(1, col.min(39) as u8)?;
+                lcds.cursor_mode_set(true, true)
+            }
+            None => lcds.cursor_mode_set(false, false),
+        }
+    }
+
+    /// Returns the second-row text when not editing.
+    fn detail(&self, menu: &Menu, selected: usize) -> String {
+        if let Some(message) = &self.message {
+            return format!(" {}", message);
+        }
+        match &menu.items[selected].action {
+            MenuAction::Info(value) => format!(" {}", value()),
+            MenuAction::Edit(setting) => format!(" {}{}", (setting.get)(), setting.unit),
+            _ if menu.len() > 1 => format!(" {}", menu.items[(selected + 1) % menu.len()].label),
+            _ => String::new(),
+        }
+    }
+
+    /// Writes a row from column 0 and blanks whatever was left after it.
+    fn write_line<T: Transport>(lcds: &mut Lcds<T>, row: u8, text: &str) -> Result<()> {
+        lcds.write_string_at_pos(row, 0, text)?;
+        let width = lcds.charset().width(text);
+        if width < 40 {
+            lcds.set_pos(row, width as u8)?;
+            lcds.erase_in_line(0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+    use crate::peripheral::input::{InputSource, MockInput};
+
+    fn menu(threshold: &Rc<Cell<i32>>) -> MenuSystem {
+        let (get, set) = (threshold.clone(), threshold.clone());
+        let root = Menu::new()
+            .action("Water now", || "Watering".to_string())
+            .setting(
+                "Threshold",
+                Setting {
+                    get: Box::new(move || get.get()),
+                    set: Box::new(move |v| set.set(v)),
+                    min: 0,
+                    max: 100,
+                    step: 5,
+                    unit: "%".to_string(),
+                },
+            )
+            .submenu("About", Menu::new().info("Version", || "1.2".to_string()));
+        let mut system = MenuSystem::new(root);
+        system.open();
+        system
+    }
+
+    fn drive(system: &mut MenuSystem, events: &[InputEvent]) {
+        let mut input = MockInput::new();
+        for &event in events {
+            input.push(event);
+        }
+        while let Some(event) = input.poll(Instant::now()) {
+            system.handle(event);
+        }
+    }
+
+    fn screen(system: &MenuSystem) -> Vec<String> {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        system.render(&mut lcds).unwrap();
+        lcds.transport().screen()
+    }
+
+    #[test]
+    fn navigation_wraps_and_enters_submenus() {
+        let threshold = Rc::new(Cell::new(40));
+        let mut system = menu(&threshold);
+        assert_eq!(screen(&system), [">Water now      ", " Threshold      "]);
+
+        drive(&mut system, &[InputEvent::Up]);
+        assert_eq!(system.selected_label(), Some("About"));
+        assert_eq!(screen(&system), ["+About          ", " Water now      "]);
+
+        drive(&mut system, &[InputEvent::Select]);
+        assert_eq!(screen(&system), [">Version        ", " 1.2            "]);
+
+        drive(&mut system, &[InputEvent::LongSelect, InputEvent::Down]);
+        assert_eq!(system.selected_label(), Some("Water now"));
+        drive(&mut system, &[InputEvent::Select]);
+        assert_eq!(screen(&system)[1], " Watering       ");
+
+        drive(&mut system, &[InputEvent::Back]);
+        assert!(!system.is_open());
+    }
+
+    #[test]
+    fn edit_saves_on_select_and_clamps() {
+        let threshold = Rc::new(Cell::new(40));
+        let mut system = menu(&threshold);
+        drive(&mut system, &[InputEvent::Down, InputEvent::Select, InputEvent::Up, InputEvent::Up]);
+        assert!(system.is_editing());
+        assert_eq!(screen(&system), [">Threshold      ", " 50%            "]);
+        assert_eq!(threshold.get(), 40, "nothing is stored before Select");
+
+        drive(&mut system, &[InputEvent::Select]);
+        assert!(!system.is_editing());
+        assert_eq!(threshold.get(), 50);
+        assert_eq!(screen(&system)[1], " Saved          ");
+
+        threshold.set(95);
+        drive(&mut system, &[InputEvent::Select, InputEvent::Up, InputEvent::Up, InputEvent::Select]);
+        assert_eq!(threshold.get(), 100);
+    }
+
+    #[test]
+    fn edit_is_discarded_on_back() {
+        let threshold = Rc::new(Cell::new(40));
+        let mut system = menu(&threshold);
+        drive(&mut system, &[InputEvent::Down, InputEvent::Select, InputEvent::Down, InputEvent::Back]);
+        assert!(!system.is_editing());
+        assert!(system.is_open());
+        assert_eq!(threshold.get(), 40);
+        assert_eq!(screen(&system)[1], " 40%            ");
+    }
+
+    #[test]
+    fn encoder_raises_values_clockwise() {
+        let threshold = Rc::new(Cell::new(40));
+        let mut system = menu(&threshold).with_encoder(true);
+        // Clockwise (Down) still moves forward through the list.
+        drive(&mut system, &[InputEvent::Down, InputEvent::Select]);
+        drive(&mut system, &[InputEvent::Down, InputEvent::Down, InputEvent::Up, InputEvent::Select]);
+        assert_eq!(threshold.get(), 45);
+    }
+
+    #[test]
+    fn edit_saturates_near_the_integer_limits() {
+        let value = Rc::new(Cell::new(i32::MAX - 1));
+        let (get, set) = (value.clone(), value.clone());
+        let root = Menu::new().setting(
+            "Offset",
+            Setting {
+                get: Box::new(move || get.get()),
+                set: Box::new(move |v| set.set(v)),
+                min: i32::MIN,
+                max: i32::MAX,
+                step: 1000,
+                unit: String::new(),
+            },
+        );
+        let mut system = MenuSystem::new(root);
+        system.open();
+        drive(&mut system, &[InputEvent::Select, InputEvent::Up, InputEvent::Select]);
+        assert_eq!(value.get(), i32::MAX);
+
+        value.set(i32::MIN + 1);
+        drive(&mut system, &[InputEvent::Select, InputEvent::Down, InputEvent::Select]);
+        assert_eq!(value.get(), i32::MIN);
+    }
+}
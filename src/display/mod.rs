@@ -0,0 +1,9 @@
+pub mod backlight;
+pub mod framebuffer;
+pub mod glyph;
+pub mod graph;
+pub mod marquee;
+pub mod menu;
+pub mod pages;
+pub mod profile;
+pub mod supervisor;
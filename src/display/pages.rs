@@ -0,0 +1,341 @@
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::peripheral::lcds::{Lcds, Result};
+use crate::peripheral::transport::Transport;
+
+/// Default time a page stays up before rotating to the next one.
+pub const DEFAULT_DWELL: Duration = Duration::from_secs(5);
+
+/// Draws one page onto the display.
+pub type RenderFn<T> = Box<dyn FnMut(&mut Lcds<T>) -> Result<()>>;
+
+struct Page<T: Transport> {
+    name: String,
+    refresh: Duration,
+    render: RenderFn<T>,
+}
+
+struct Alert<T: Transport> {
+    page: Page<T>,
+    until: Instant,
+    shown: bool,
+}
+
+/// Rotates a set of registered pages on the two-row display.
+///
+/// Each page is a render function with its own refresh interval. Pages
+/// rotate every `dwell` while auto-rotation is on, and `next`/`prev` move
+/// between them on button input. An alert preempts rotation until it
+/// expires or is dismissed, after which the current page is shown again.
+pub struct PageManager<T: Transport> {
+    pages: Vec<Page<T>>,
+    current: usize,
+    dwell: Duration,
+    auto_rotate: bool,
+    alert: Option<Alert<T>>,
+    shown_at: Option<Instant>,
+    rendered_at: Option<Instant>,
+}
+
+impl<T: Transport> PageManager<T> {
+    /// Creates an empty page manager rotating every `dwell`.
+    pub fn new(dwell: Duration) -> Self {
+        Self {
+            pages: Vec::new(),
+            current: 0,
+            dwell,
+            auto_rotate: true,
+            alert: None,
+            shown_at: None,
+            rendered_at: None,
+        }
+    }
+
+    /// Registers a page re-rendered every `refresh` and returns its index.
+    pub fn add_page<F>(&mut self, name: &str, refresh: Duration, render: F) -> usize
+    where
+        F: FnMut(&mut Lcds<T>) -> Result<()> + 'static,
+    {
+        self.pages.push(Page {
+            name: name.to_string(),
+            refresh,
+            render: Box::new(render),
+        });
+        self.pages.len() - 1
+    }
+
+    /// Turns timed rotation on or off. Manual navigation works either way.
+    pub fn set_auto_rotate(&mut self, auto_rotate: bool) {
+        self.auto_rotate = auto_rotate;
+    }
+
+    /// Returns the index of the current page.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the name of the page or alert on screen.
+    pub fn current_name(&self) -> Option<&str> {
+        match &self.alert {
+            Some(alert) => Some(&alert.page.name),
+            None => self.pages.get(self.current).map(|p| p.name.as_str()),
+        }
+    }
+
+    /// Returns true if an alert is preempting the pages.
+    pub fn alert_active(&self) -> bool {
+        self.alert.is_some()
+    }
+
+    /// Switches to the page at `index`; the switch is drawn on the next `tick`.
+    pub fn show(&mut self, index: usize) {
+        if index < self.pages.len() {
+            self.current = index;
+            self.shown_at = None;
+        }
+    }
+
+    /// Advances to the next page.
+    pub fn next(&mut self) {
+        if !self.pages.is_empty() {
+            self.show((self.current + 1) % self.pages.len());
+        }
+    }
+
+    /// Goes back to the previous page.
+    pub fn prev(&mut self) {
+        if !self.pages.is_empty() {
+            self.show((self.current + self.pages.len() - 1) % self.pages.len());
+        }
+    }
+
+    /// Shows an alert page for `duration`, re-rendered every `refresh`,
+    /// replacing any alert already up.
+    pub fn alert<F>(&mut self, name: &str, duration: Duration, refresh: Duration, now: Instant, render: F)
+    where
+        F: FnMut(&mut Lcds<T>) -> Result<()> + 'static,
+    {
+        self.alert = Some(Alert {
+            page: Page {
+                name: name.to_string(),
+                refresh,
+                render: Box::new(render),
+            },
+            until: now + duration,
+            shown: false,
+        });
+        self.rendered_at = None;
+    }
+
+    /// Removes the alert, returning to the current page on the next `tick`.
+    pub fn dismiss_alert(&mut self) {
+        if self.alert.take().is_some() {
+            self.shown_at = None;
+        }
+    }
+
+    /// Rotates, expires alerts and redraws whatever is due.
+    /// Returns true if anything was drawn.
+    ///
+    /// # Errors
+    /// * Returns the driver error if clearing or rendering fails; the page is
+    ///   then rendered again on the next `tick` rather than after `refresh`.
+    pub fn tick(&mut self, lcds: &mut Lcds<T>, now: Instant) -> Result<bool> {
+        if self.alert.as_ref().is_some_and(|a| now >= a.until) {
+            self.dismiss_alert();
+        }
+
+        if let Some(alert) = self.alert.as_mut() {
+            if self.rendered_at.is_some_and(|t| now.duration_since(t) < alert.page.refresh) {
+                return Ok(false);
+            }
+            if !alert.shown {
+                debug!("showing alert {}", alert.page.name);
+                lcds.display_clear()?;
+                alert.shown = true;
+            }
+            let drawn = (alert.page.render)(lcds);
+            self.rendered_at = drawn.is_ok().then_some(now);
+            return drawn.map(|()| true);
+        }
+
+        if self.pages.is_empty() {
+            return Ok(false);
+        }
+        if self.auto_rotate && self.shown_at.is_some_and(|t| now.duration_since(t) >= self.dwell) {
+            self.next();
+        }
+
+        let page = &mut self.pages[self.current];
+        let fresh = self.shown_at.is_none();
+        let due = fresh || self.rendered_at.is_none_or(|t| now.duration_since(t) >= page.refresh);
+        if !due {
+            return Ok(false);
+        }
+        if fresh {
+            debug!("showing page {}", page.name);
+            lcds.display_clear()?;
+            self.shown_at = Some(now);
+        }
+        let drawn = (page.render)(lcds);
+        self.rendered_at = drawn.is_ok().then_some(now);
+        drawn.map(|()| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+    use crate::peripheral::lcds::{CommandDelays, LcdsError};
+
+    const DWELL: Duration = Duration::from_secs(5);
+    const REFRESH: Duration = Duration::from_secs(1);
+
+    fn lcds() -> Lcds<LcdsEmulator> {
+        let no_delays = CommandDelays {
+            reset: Duration::ZERO,
+            clear: Duration::ZERO,
+            eeprom: Duration::ZERO,
+        };
+        Lcds::new(LcdsEmulator::new_2x16()).with_delays(no_delays)
+    }
+
+    /// A page that writes `text` and counts its renders in the returned cell.
+    fn counted(text: &'static str) -> (Rc<Cell<u32>>, impl FnMut(&mut Lcds<LcdsEmulator>) -> Result<()>) {
+        let renders = Rc::new(Cell::new(0));
+        let count = renders.clone();
+        let render = move |lcds: &mut Lcds<LcdsEmulator>| {
+            count.set(count.get() + 1);
+            lcds.write_string_at_pos(0, 0, text)
+        };
+        (renders, render)
+    }
+
+    fn manager() -> PageManager<LcdsEmulator> {
+        let mut pages = PageManager::new(DWELL);
+        for name in ["Moisture", "Temp", "Light"] {
+            pages.add_page(name, REFRESH, move |lcds| lcds.write_string_at_pos(0, 0, name));
+        }
+        pages
+    }
+
+    #[test]
+    fn pages_rotate_after_the_dwell() {
+        let mut lcds = lcds();
+        let mut pages = manager();
+        let start = Instant::now();
+        assert!(pages.tick(&mut lcds, start).unwrap());
+        assert_eq!(lcds.transport().line(0).trim_end(), "Moisture");
+        pages.tick(&mut lcds, start + DWELL - REFRESH).unwrap();
+        assert_eq!(pages.current(), 0);
+        pages.tick(&mut lcds, start + DWELL).unwrap();
+        assert_eq!(pages.current_name(), Some("Temp"));
+        // The old page was cleared rather than overwritten.
+        assert_eq!(lcds.transport().line(0).trim_end(), "Temp");
+    }
+
+    #[test]
+    fn manual_navigation_wraps_and_works_without_rotation() {
+        let mut lcds = lcds();
+        let mut pages = manager();
+        pages.set_auto_rotate(false);
+        let start = Instant::now();
+        pages.tick(&mut lcds, start).unwrap();
+        pages.tick(&mut lcds, start + DWELL * 3).unwrap();
+        assert_eq!(pages.current(), 0);
+        pages.prev();
+        assert_eq!(pages.current(), 2);
+        pages.next();
+        pages.next();
+        assert_eq!(pages.current(), 1);
+        pages.tick(&mut lcds, start + DWELL * 3).unwrap();
+        assert_eq!(lcds.transport().line(0).trim_end(), "Temp");
+    }
+
+    #[test]
+    fn pages_are_rerendered_at_their_refresh_rate() {
+        let mut lcds = lcds();
+        let mut pages = PageManager::new(DWELL);
+        let (renders, render) = counted("Moisture");
+        pages.add_page("Moisture", REFRESH, render);
+        let start = Instant::now();
+        pages.tick(&mut lcds, start).unwrap();
+        assert!(!pages.tick(&mut lcds, start + REFRESH / 2).unwrap());
+        assert!(pages.tick(&mut lcds, start + REFRESH).unwrap());
+        assert_eq!(renders.get(), 2);
+    }
+
+    #[test]
+    fn alert_preempts_and_returns_to_the_page() {
+        let mut lcds = lcds();
+        let mut pages = manager();
+        let start = Instant::now();
+        pages.tick(&mut lcds, start).unwrap();
+        pages.alert("Dry", DWELL * 2, DWELL * 2, start, |lcds| lcds.write_string_at_pos(0, 0, "DRY!"));
+        assert!(pages.tick(&mut lcds, start).unwrap());
+        assert_eq!(lcds.transport().line(0).trim_end(), "DRY!");
+        // No rotation underneath the alert.
+        assert!(!pages.tick(&mut lcds, start + DWELL).unwrap());
+        assert_eq!(pages.current_name(), Some("Dry"));
+
+        pages.tick(&mut lcds, start + DWELL * 2).unwrap();
+        assert!(!pages.alert_active());
+        assert_eq!(pages.current_name(), Some("Moisture"));
+        assert_eq!(lcds.transport().line(0).trim_end(), "Moisture");
+    }
+
+    #[test]
+    fn dismissed_alert_redraws_the_current_page() {
+        let mut lcds = lcds();
+        let mut pages = manager();
+        let start = Instant::now();
+        pages.tick(&mut lcds, start).unwrap();
+        pages.alert("Dry", DWELL, DWELL, start, |lcds| lcds.write_string_at_pos(0, 0, "DRY!"));
+        pages.tick(&mut lcds, start).unwrap();
+        pages.dismiss_alert();
+        assert!(pages.tick(&mut lcds, start).unwrap());
+        assert_eq!(lcds.transport().line(0).trim_end(), "Moisture");
+    }
+
+    #[test]
+    fn alert_is_rerendered_at_its_refresh_rate() {
+        let mut lcds = lcds();
+        let mut pages = manager();
+        let (renders, render) = counted("Pump on");
+        let start = Instant::now();
+        pages.alert("Pump", DWELL, REFRESH, start, render);
+        pages.tick(&mut lcds, start).unwrap();
+        assert!(!pages.tick(&mut lcds, start + REFRESH / 2).unwrap());
+        assert!(pages.tick(&mut lcds, start + REFRESH).unwrap());
+        assert_eq!(renders.get(), 2);
+    }
+
+    #[test]
+    fn failed_render_is_retried_on_the_next_tick() {
+        let mut lcds = lcds();
+        let mut pages = PageManager::new(DWELL);
+        let failing = Rc::new(Cell::new(false));
+        let fail = failing.clone();
+        pages.add_page("Moisture", DWELL * 10, move |lcds| {
+            if fail.get() {
+                return Err(LcdsError::RowRange(9));
+            }
+            lcds.write_string_at_pos(0, 0, "Moisture")
+        });
+        let start = Instant::now();
+        pages.tick(&mut lcds, start).unwrap();
+        failing.set(true);
+        pages.show(0);
+        assert!(pages.tick(&mut lcds, start + REFRESH).is_err());
+        // Well within the refresh interval, but the screen was left blank.
+        failing.set(false);
+        assert!(pages.tick(&mut lcds, start + REFRESH * 2).unwrap());
+        assert_eq!(lcds.transport().line(0).trim_end(), "Moisture");
+    }
+}
@@ -0,0 +1,397 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use log::info;
+
+use super::glyph::{Glyph, Icon, SLOTS};
+use crate::peripheral::lcds::{CursorMode, Lcds, Result};
+use crate::peripheral::transport::Transport;
+use crate::peripheral::uart::{BAUD_RATES, baud_rate_for_index};
+
+/// Line wrapping mode of the display (0: 16 characters, 1: 40 characters).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    Wrap16,
+    Wrap40,
+}
+
+/// Interface the PmodCLS listens on after power-up (the MD2..MD0 selection).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommMode {
+    Uart2400 = 0,
+    Uart4800 = 1,
+    Uart9600 = 2,
+    /// UART at the baud rate stored with `save_br`.
+    UartEeprom = 3,
+    /// TWI at address 0x48.
+    Twi = 4,
+    /// TWI at the address stored with `save_twi_addr`.
+    TwiEeprom = 5,
+    Spi = 6,
+    /// Interface and settings all taken from EEPROM.
+    Eeprom = 7,
+}
+
+impl CommMode {
+    const NAMES: [(&'static str, CommMode); 8] = [
+        ("uart_2400", CommMode::Uart2400),
+        ("uart_4800", CommMode::Uart4800),
+        ("uart_9600", CommMode::Uart9600),
+        ("uart_eeprom", CommMode::UartEeprom),
+        ("twi", CommMode::Twi),
+        ("twi_eeprom", CommMode::TwiEeprom),
+        ("spi", CommMode::Spi),
+        ("eeprom", CommMode::Eeprom),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::NAMES.iter().find(|(_, m)| *m == self).unwrap().0
+    }
+}
+
+/// A line of a profile config that could not be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ProfileParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "display profile line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ProfileParseError {}
+
+/// Everything the PmodCLS can remember across power cycles.
+///
+/// `apply_and_persist` programs the live settings and then stores each one
+/// in the display's EEPROM, so every unit boots its display identically.
+/// Profiles are described in the app config as `key = value` lines,
+/// optionally under a `[display]` section:
+///
+/// ```text
+/// [display]
+/// cursor = off            # off | on | blink
+/// display_mode = 16       # 16 | 40
+/// comm = spi              # uart_2400 .. eeprom, see CommMode; omit to keep it
+/// baud = 9600             # stored UART rate, see uart::BAUD_RATES
+/// glyph_table = 0         # EEPROM table (0-3) for the glyphs
+/// glyph.0 = droplet       # an Icon name, or eight row bytes:
+/// glyph.1 = 0x04,0x0E,0x1F,0x1F,0x1F,0x0E,0x04,0x00
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayProfile {
+    pub cursor: CursorMode,
+    pub display_mode: DisplayMode,
+    /// Interface to store for the next power-up, or `None` to leave the
+    /// jumper or previously stored selection alone.
+    pub comm: Option<CommMode>,
+    /// Baud rate index (0-6) to store, or `None` to leave it unchanged.
+    pub baud: Option<u8>,
+    /// EEPROM character table the glyphs are saved into (0-3).
+    pub glyph_table: u8,
+    pub glyphs: [Option<Glyph>; SLOTS],
+}
+
+impl Default for DisplayProfile {
+    fn default() -> Self {
+        Self {
+            cursor: CursorMode::Off,
+            display_mode: DisplayMode::Wrap16,
+            comm: None,
+            baud: None,
+            glyph_table: 0,
+            glyphs: [None; SLOTS],
+        }
+    }
+}
+
+impl DisplayProfile {
+    /// Programs the profile into the display without persisting it.
+    ///
+    /// Only the glyph slots the profile sets are defined; the others are left
+    /// to whatever loaded them, such as a `GlyphCache`.
+    pub fn apply<T: Transport>(&self, lcds: &mut Lcds<T>) -> Result<()> {
+        match self.cursor {
+            CursorMode::Off => lcds.cursor_mode_set(false, false)?,
+            CursorMode::On => lcds.cursor_mode_set(true, false)?,
+            CursorMode::Blink => lcds.cursor_mode_set(true, true)?,
+        }
+        lcds.display_mode(self.display_mode == DisplayMode::Wrap16)?;
+        for (pos, glyph) in self.glyphs.iter().enumerate() {
+            if let Some(glyph) = glyph {
+                lcds.define_user_char(glyph.rows(), pos as u8)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Programs the profile and saves every setting to the display's EEPROM.
+    ///
+    /// Each save is preceded by its own write-enable, since the controller
+    /// clears the enable after every EEPROM write. The communication mode, if
+    /// set, is saved last so the link stays up while the other settings are
+    /// written; it takes effect at the next power-up.
+    pub fn apply_and_persist<T: Transport>(&self, lcds: &mut Lcds<T>) -> Result<()> {
+        self.apply(lcds)?;
+
+        lcds.eeprom_wr_en()?;
+        lcds.save_cursor_to_eeprom(self.cursor as u8)?;
+        lcds.eeprom_wr_en()?;
+        lcds.save_display_to_eeprom(self.display_mode as u8)?;
+        if self.glyphs.iter().any(Option::is_some) {
+            lcds.eeprom_wr_en()?;
+            lcds.save_ram_to_eeprom(self.glyph_table)?;
+        }
+        if let Some(baud) = self.baud {
+            lcds.eeprom_wr_en()?;
+            lcds.save_br(baud)?;
+        }
+        if let Some(comm) = self.comm {
+            lcds.eeprom_wr_en()?;
+            lcds.save_comm_to_eeprom(comm as u8)?;
+        }
+        info!("display profile persisted: {:?}", self);
+        Ok(())
+    }
+
+    /// Reads a profile from a config file.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::result::Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self::from_config(&fs::read_to_string(path)?)?)
+    }
+
+    /// Parses a profile from config text. Unset keys keep their defaults.
+    pub fn from_config(text: &str) -> std::result::Result<Self, ProfileParseError> {
+        let mut profile = Self::default();
+        let mut in_display = true;
+        for (index, raw) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let err = |message: String| ProfileParseError { line: line_no, message };
+            let line = raw.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_display = section.trim() == "display";
+                continue;
+            }
+            if !in_display {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| err(format!("expected `key = value`, found {:?}", line)))?;
+            profile.set(key, value).map_err(err)?;
+        }
+        Ok(profile)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        match key {
+            "cursor" => {
+                self.cursor = match value {
+                    "off" => CursorMode::Off,
+                    "on" => CursorMode::On,
+                    "blink" => CursorMode::Blink,
+                    _ => return Err(format!("cursor must be off, on or blink, found {:?}", value)),
+                }
+            }
+            "display_mode" => {
+                self.display_mode = match value {
+                    "16" => DisplayMode::Wrap16,
+                    "40" => DisplayMode::Wrap40,
+                    _ => return Err(format!("display_mode must be 16 or 40, found {:?}", value)),
+                }
+            }
+            "comm" => {
+                let mode = CommMode::NAMES
+                    .iter()
+                    .find(|(name, _)| *name == value)
+                    .map(|&(_, mode)| mode)
+                    .ok_or_else(|| format!("unknown comm mode {:?}", value))?;
+                self.comm = Some(mode);
+            }
+            "baud" => {
+                let rate: u32 = value.parse().map_err(|_| format!("invalid baud rate {:?}", value))?;
+                let index = BAUD_RATES
+                    .iter()
+                    .position(|&r| r == rate)
+                    .ok_or_else(|| format!("baud rate {} is not one of {:?}", rate, BAUD_RATES))?;
+                self.baud = Some(index as u8);
+            }
+            "glyph_table" => {
+                self.glyph_table = value
+                    .parse()
+                    .ok()
+                    .filter(|&t| t <= 3)
+                    .ok_or_else(|| format!("glyph_table must be 0-3, found {:?}", value))?
+            }
+            _ => {
+                let slot = key
+                    .strip_prefix("glyph.")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .filter(|&s| s < SLOTS)
+                    .ok_or_else(|| format!("unknown key {:?}", key))?;
+                self.glyphs[slot] = Some(parse_glyph(value)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats the profile as config lines accepted by `from_config`.
+    pub fn to_config(&self) -> String {
+        let mut out = String::from("[display]\n");
+        let cursor = match self.cursor {
+            CursorMode::Off => "off",
+            CursorMode::On => "on",
+            CursorMode::Blink => "blink",
+        };
+        out += &format!("cursor = {}\n", cursor);
+        let mode = match self.display_mode {
+            DisplayMode::Wrap16 => 16,
+            DisplayMode::Wrap40 => 40,
+        };
+        out += &format!("display_mode = {}\n", mode);
+        if let Some(comm) = self.comm {
+            out += &format!("comm = {}\n", comm.name());
+        }
+        if let Some(rate) = self.baud.and_then(baud_rate_for_index) {
+            out += &format!("baud = {}\n", rate);
+        }
+        out += &format!("glyph_table = {}\n", self.glyph_table);
+        for (slot, glyph) in self.glyphs.iter().enumerate() {
+            if let Some(glyph) = glyph {
+                let rows: Vec<String> = glyph.rows().iter().map(|r| format!("0x{:02X}", r)).collect();
+                out += &format!("glyph.{} = {}\n", slot, rows.join(","));
+            }
+        }
+        out
+    }
+}
+
+/// Parses an icon name or a comma-separated list of eight row bytes.
+fn parse_glyph(value: &str) -> std::result::Result<Glyph, String> {
+    if let Some(icon) = Icon::ALL.iter().find(|i| format!("{:?}", i).eq_ignore_ascii_case(value)) {
+        return Ok(icon.glyph());
+    }
+    let rows: Vec<u8> = value
+        .split(',')
+        .map(|r| {
+            let r = r.trim();
+            match r.strip_prefix("0x").or_else(|| r.strip_prefix("0X")) {
+                Some(hex) => u8::from_str_radix(hex, 16),
+                None => r.parse(),
+            }
+        })
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| format!("glyph must be an icon name or eight row bytes, found {:?}", value))?;
+    let rows: [u8; 8] = rows
+        .try_into()
+        .map_err(|_| format!("glyph needs exactly eight row bytes, found {:?}", value))?;
+    Ok(Glyph(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::display::glyph::DROPLET;
+    use crate::peripheral::lcds::CommandDelays;
+    use crate::peripheral::transport::MemoryTransport;
+
+    fn lcds() -> Lcds<MemoryTransport> {
+        let no_delays = CommandDelays {
+            reset: Duration::ZERO,
+            clear: Duration::ZERO,
+            eeprom: Duration::ZERO,
+        };
+        Lcds::new(MemoryTransport::new()).with_delays(no_delays)
+    }
+
+    fn commands(lcds: &Lcds<MemoryTransport>) -> Vec<String> {
+        lcds.transport()
+            .transfers()
+            .iter()
+            .map(|t| String::from_utf8(t.clone()).unwrap().replace('\x1b', "ESC"))
+            .collect()
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let mut custom = DROPLET;
+        custom.0[7] = 0x1F;
+        let profile = DisplayProfile {
+            cursor: CursorMode::Blink,
+            display_mode: DisplayMode::Wrap40,
+            comm: Some(CommMode::UartEeprom),
+            baud: Some(6),
+            glyph_table: 2,
+            glyphs: [Some(DROPLET), None, None, Some(custom), None, None, None, None],
+        };
+        assert_eq!(DisplayProfile::from_config(&profile.to_config()), Ok(profile));
+        let default = DisplayProfile::default();
+        assert_eq!(DisplayProfile::from_config(&default.to_config()), Ok(default));
+    }
+
+    #[test]
+    fn config_reads_icons_and_skips_other_sections() {
+        let text = "[sensor]\ncomm = bogus\n\n[display]\ncomm = twi  # address 0x48\nglyph.0 = Droplet\n";
+        let profile = DisplayProfile::from_config(text).unwrap();
+        assert_eq!(profile.comm, Some(CommMode::Twi));
+        assert_eq!(profile.glyphs[0], Some(DROPLET));
+        assert_eq!(profile.cursor, CursorMode::Off);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let cases = [
+            ("cursor = on\ncursor = sideways", 2),
+            ("[display]\n\nbaud = 12345", 3),
+            ("glyph.8 = droplet", 1),
+            ("glyph.0 = 1,2,3", 1),
+            ("glyph_table = 4", 1),
+            ("# comment\ncomm", 2),
+        ];
+        for (text, line) in cases {
+            let err = DisplayProfile::from_config(text).unwrap_err();
+            assert_eq!(err.line, line, "{:?}: {}", text, err);
+        }
+    }
+
+    #[test]
+    fn persist_leaves_comm_alone_unless_set() {
+        let mut lcds = lcds();
+        DisplayProfile::default().apply_and_persist(&mut lcds).unwrap();
+        assert_eq!(
+            commands(&lcds),
+            ["ESC[0c", "ESC[0h", "ESC[0w", "ESC[0n", "ESC[0w", "ESC[0o"]
+        );
+    }
+
+    #[test]
+    fn persist_write_enables_every_save_and_stores_comm_last() {
+        let mut lcds = lcds();
+        let profile = DisplayProfile {
+            cursor: CursorMode::On,
+            display_mode: DisplayMode::Wrap40,
+            comm: Some(CommMode::UartEeprom),
+            baud: Some(4),
+            glyph_table: 1,
+            glyphs: [Some(DROPLET), None, None, None, None, None, None, None],
+        };
+        profile.apply_and_persist(&mut lcds).unwrap();
+        let commands = commands(&lcds);
+        assert_eq!(commands[..2], ["ESC[1c", "ESC[1h"]);
+        assert!(commands[2].ends_with("0dESC[3p"), "{}", commands[2]);
+        assert_eq!(
+            commands[3..],
+            ["ESC[0w", "ESC[1n", "ESC[0w", "ESC[1o", "ESC[0w", "ESC[1t", "ESC[0w", "ESC[4b", "ESC[0w", "ESC[3m"]
+        );
+    }
+}
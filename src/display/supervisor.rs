@@ -0,0 +1,325 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{debug, warn};
+use prometheus::{IntCounter, IntGauge, Opts, Registry};
+
+use super::framebuffer::Framebuffer;
+use super::profile::DisplayProfile;
+use crate::peripheral::lcds::{DEFAULT_DEVICE, Lcds, LcdsError, Result};
+use crate::peripheral::transport::Transport;
+
+/// Default time between re-asserting the display settings.
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default number of consecutive transport errors that trigger a reset.
+pub const DEFAULT_RESET_AFTER: u32 = 3;
+
+/// Wait before retrying a failed recovery; doubles per failure.
+const RETRY_MIN: Duration = Duration::from_secs(1);
+
+/// Longest wait between recovery attempts.
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// What a supervisor `tick` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// Nothing was due, or a failed recovery is waiting to be retried.
+    Idle,
+    /// Settings, the profile's own glyphs and the last `display_set` state
+    /// were re-asserted and the framebuffer redrawn. User characters outside
+    /// the profile are left alone, but a brown-out since the last check would
+    /// have wiped them, so a `GlyphCache` must be invalidated here too.
+    Reasserted,
+    /// The display was reset, reconfigured, set back to its last display and
+    /// backlight state and redrawn. Anything else that tracks display state,
+    /// such as a `GlyphCache`, must be invalidated.
+    Recovered,
+}
+
+/// Keeps the display configured and recovers it after brown-outs.
+///
+/// A PmodCLS that loses power comes back blank with its default settings,
+/// and the driver has no way to read its state. The supervisor therefore
+/// re-applies the profile and the last display and backlight state, and
+/// redraws the framebuffer, every check interval. It resets the display once
+/// transport errors keep piling up; a failed reset is retried with a backoff
+/// rather than on every tick.
+pub struct Supervisor {
+    profile: DisplayProfile,
+    interval: Duration,
+    reset_after: u32,
+    consecutive_errors: u32,
+    last_check: Option<Instant>,
+    retry_wait: Duration,
+    retry_at: Option<Instant>,
+    metrics: Metrics,
+}
+
+/// Health metrics of one display, labelled with its `device` name.
+struct Metrics {
+    errors: IntCounter,
+    recoveries: IntCounter,
+    last_recovery: IntGauge,
+}
+
+impl Metrics {
+    fn new(device: &str) -> Self {
+        let opts = |name: &str, help: &str| Opts::new(name, help).const_label("device", device);
+        Self {
+            errors: IntCounter::with_opts(opts("lcds_display_errors_total", "Transport errors seen on the display"))
+                .unwrap(),
+            recoveries: IntCounter::with_opts(opts(
+                "lcds_display_recoveries_total",
+                "Display resets after repeated errors",
+            ))
+            .unwrap(),
+            last_recovery: IntGauge::with_opts(opts(
+                "lcds_display_last_recovery_timestamp_seconds",
+                "Unix time of the last display recovery",
+            ))
+            .unwrap(),
+        }
+    }
+}
+
+impl Supervisor {
+    /// Creates a supervisor that keeps the display configured per `profile`.
+    pub fn new(profile: DisplayProfile) -> Self {
+        Self {
+            profile,
+            interval: DEFAULT_CHECK_INTERVAL,
+            reset_after: DEFAULT_RESET_AFTER,
+            consecutive_errors: 0,
+            last_check: None,
+            retry_wait: Duration::ZERO,
+            retry_at: None,
+            metrics: Metrics::new(DEFAULT_DEVICE),
+        }
+    }
+
+    /// Sets the time between re-asserting the settings.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how many consecutive transport errors trigger a reset.
+    pub fn with_reset_after(mut self, errors: u32) -> Self {
+        self.reset_after = errors.max(1);
+        self
+    }
+
+    /// Labels the metrics with the `device` name of the supervised display,
+    /// so supervisors of several displays can share a registry.
+    ///
+    /// Replaces the metrics with fresh ones, so call it before registering.
+    pub fn with_device(mut self, device: &str) -> Self {
+        self.metrics = Metrics::new(device);
+        self
+    }
+
+    /// Registers the error and recovery metrics with a Prometheus registry.
+    pub fn register_metrics(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.metrics.errors.clone()))?;
+        registry.register(Box::new(self.metrics.recoveries.clone()))?;
+        registry.register(Box::new(self.metrics.last_recovery.clone()))
+    }
+
+    /// Returns the number of transport errors in a row.
+    pub fn consecutive_errors(&self) -> u32 {
+        self.consecutive_errors
+    }
+
+    /// Records the outcome of a display operation made outside the supervisor
+    /// and passes it through. Only transport errors count towards a reset.
+    pub fn observe<R>(&mut self, result: Result<R>) -> Result<R> {
+        match &result {
+            Ok(_) => self.consecutive_errors = 0,
+            Err(LcdsError::Transport(e)) => {
+                self.metrics.errors.inc();
+                self.consecutive_errors += 1;
+                warn!("display error {} of {}: {}", self.consecutive_errors, self.reset_after, e);
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Recovers the display if too many errors occurred, or re-asserts its
+    /// settings if the check interval has passed.
+    ///
+    /// # Errors
+    /// * Returns the driver error if the display could not be reached; it
+    ///   counts towards the next reset. A failed reset is retried once its
+    ///   backoff has passed, waiting from 1 s up to a minute.
+    pub fn tick<T: Transport>(
+        &mut self,
+        lcds: &mut Lcds<T>,
+        framebuffer: &mut Framebuffer,
+        now: Instant,
+    ) -> Result<Check> {
+        if self.consecutive_errors >= self.reset_after {
+            if self.retry_at.is_some_and(|t| now < t) {
+                return Ok(Check::Idle);
+            }
+            let result = self.recover(lcds, framebuffer);
+            if let Err(e) = self.observe(result) {
+                self.retry_wait = (self.retry_wait * 2).clamp(RETRY_MIN, RETRY_MAX);
+                self.retry_at = Some(now + self.retry_wait);
+                warn!("display recovery failed, retrying in {:?}", self.retry_wait);
+                return Err(e);
+            }
+            self.retry_wait = Duration::ZERO;
+            self.retry_at = None;
+            self.last_check = Some(now);
+            self.metrics.recoveries.inc();
+            let unix = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            self.metrics.last_recovery.set(unix as i64);
+            warn!("display reset and redrawn");
+            return Ok(Check::Recovered);
+        }
+        if self.last_check.is_some_and(|t| now.duration_since(t) < self.interval) {
+            return Ok(Check::Idle);
+        }
+        let result = self.reassert(lcds, framebuffer);
+        self.observe(result)?;
+        self.last_check = Some(now);
+        debug!("display settings re-asserted");
+        Ok(Check::Reasserted)
+    }
+
+    /// Re-applies the profile, which defines only the glyph slots it sets,
+    /// and the display and backlight state the driver last sent.
+    fn reassert<T: Transport>(&self, lcds: &mut Lcds<T>, framebuffer: &mut Framebuffer) -> Result<()> {
+        self.profile.apply(lcds)?;
+        if let Some((display, backlight)) = lcds.display_state() {
+            lcds.display_set(display, backlight)?;
+        }
+        framebuffer.invalidate();
+        framebuffer.flush(lcds).map(|_| ())
+    }
+
+    fn recover<T: Transport>(&self, lcds: &mut Lcds<T>, framebuffer: &mut Framebuffer) -> Result<()> {
+        lcds.reset()?;
+        lcds.display_clear()?;
+        self.reassert(lcds, framebuffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+    use crate::peripheral::lcds::CommandDelays;
+
+    /// An emulated display whose bus can be taken down.
+    struct Flaky {
+        emu: LcdsEmulator,
+        down: bool,
+    }
+
+    impl Transport for Flaky {
+        type Error = io::Error;
+
+        fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), Self::Error> {
+            if self.down {
+                return Err(io::Error::other("bus down"));
+            }
+            self.emu.feed(bytes);
+            Ok(())
+        }
+    }
+
+    fn setup() -> (Supervisor, Lcds<Flaky>, Framebuffer) {
+        let flaky = Flaky {
+            emu: LcdsEmulator::new_2x16(),
+            down: false,
+        };
+        let no_delays = CommandDelays {
+            reset: Duration::ZERO,
+            clear: Duration::ZERO,
+            eeprom: Duration::ZERO,
+        };
+        let lcds = Lcds::new(flaky).with_delays(no_delays);
+        let mut framebuffer = Framebuffer::new(16);
+        framebuffer.write_str(0, 0, "Moisture 42%");
+        (Supervisor::new(DisplayProfile::default()).with_reset_after(2), lcds, framebuffer)
+    }
+
+    #[test]
+    fn reasserts_once_per_interval() {
+        let (mut supervisor, mut lcds, mut framebuffer) = setup();
+        let t0 = Instant::now();
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, t0).unwrap(), Check::Reasserted);
+        assert_eq!(lcds.transport().emu.line(0), "Moisture 42%    ");
+        let soon = t0 + Duration::from_secs(10);
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, soon).unwrap(), Check::Idle);
+        let later = t0 + DEFAULT_CHECK_INTERVAL;
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, later).unwrap(), Check::Reasserted);
+    }
+
+    #[test]
+    fn failed_recovery_backs_off() {
+        let (mut supervisor, mut lcds, mut framebuffer) = setup();
+        let t0 = Instant::now();
+        lcds.transport_mut().down = true;
+        for _ in 0..2 {
+            assert!(supervisor.observe(lcds.display_clear()).is_err());
+        }
+
+        assert!(supervisor.tick(&mut lcds, &mut framebuffer, t0).is_err());
+        let early = t0 + Duration::from_millis(500);
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, early).unwrap(), Check::Idle);
+        assert!(supervisor.tick(&mut lcds, &mut framebuffer, t0 + RETRY_MIN).is_err());
+        let early = t0 + RETRY_MIN + Duration::from_millis(1500);
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, early).unwrap(), Check::Idle);
+
+        lcds.transport_mut().down = false;
+        let retry = t0 + RETRY_MIN * 3;
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, retry).unwrap(), Check::Recovered);
+        assert_eq!(supervisor.consecutive_errors(), 0);
+        assert_eq!(lcds.transport().emu.line(0), "Moisture 42%    ");
+    }
+
+    #[test]
+    fn recovery_restores_the_backlight_state() {
+        let (mut supervisor, mut lcds, mut framebuffer) = setup();
+        let t0 = Instant::now();
+        lcds.display_set(true, false).unwrap();
+        assert!(!lcds.transport().emu.backlight_on());
+
+        lcds.transport_mut().down = true;
+        for _ in 0..2 {
+            assert!(supervisor.observe(lcds.display_clear()).is_err());
+        }
+        lcds.transport_mut().down = false;
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, t0).unwrap(), Check::Recovered);
+        assert!(lcds.transport().emu.display_on());
+        assert!(!lcds.transport().emu.backlight_on());
+    }
+
+    #[test]
+    fn reassert_restores_the_display_state_after_a_brown_out() {
+        let (mut supervisor, mut lcds, mut framebuffer) = setup();
+        lcds.display_set(true, false).unwrap();
+        // A brown-out brings the controller back with its defaults.
+        lcds.transport_mut().emu.feed(b"\x1b[0*");
+        assert!(lcds.transport().emu.backlight_on());
+        let t0 = Instant::now();
+        assert_eq!(supervisor.tick(&mut lcds, &mut framebuffer, t0).unwrap(), Check::Reasserted);
+        assert!(!lcds.transport().emu.backlight_on());
+    }
+
+    #[test]
+    fn device_label_lets_several_supervisors_share_a_registry() {
+        let registry = Registry::new();
+        let supervisor = || Supervisor::new(DisplayProfile::default());
+        supervisor().register_metrics(&registry).unwrap();
+        supervisor().with_device("spidev0.1").register_metrics(&registry).unwrap();
+        Framebuffer::new(16).register_metrics(&registry).unwrap();
+        Framebuffer::new(16).with_device("spidev0.1").register_metrics(&registry).unwrap();
+        assert!(supervisor().register_metrics(&registry).is_err());
+    }
+}
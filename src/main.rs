@@ -2,68 +2,34 @@
 
 use std::error::Error;
 
-use prometheus::{Encoder, TextEncoder, IntCounter, register_int_counter};
-use rppal::spi::{Bus, Mode, Segment, SlaveSelect, Spi};
+use prometheus::{Encoder, TextEncoder};
+use rppal::spi::{Bus, SlaveSelect};
 
-// Instruction set.
-const WRITE: u8 = 0b0010; // Write data, starting at the selected address.
-const READ: u8 = 0b0011; // Read data, starting at the selected address.
-const RDSR: u8 = 0b0101; // Read the STATUS register.
-const WREN: u8 = 0b0110; // Set the write enable latch (enable write operations).
-
-const WIP: u8 = 1; // Write-In-Process bit mask for the STATUS register.
+use plant_sensor::peripheral::eeprom::Eeprom25aa1024;
 
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
 
-    // Configure the SPI peripheral. The 24AA1024 clocks in data on the first
-    // rising edge of the clock signal (SPI mode 0). At 3.3 V, clock speeds of up
-    // to 10 MHz are supported.
-    let mut spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 8_000_000, Mode::Mode0)?;
-
-    // Prometheus metrics setup
-    let write_counter = register_int_counter!("spi_write_total", "Total SPI write operations").unwrap();
-    let read_counter = register_int_counter!("spi_read_total", "Total SPI read operations").unwrap();
-
-    // Set the write enable latch using the WREN instruction. This is required
-    // before any data can be written. The write enable latch is automatically
-    // reset after a WRITE instruction is successfully executed.
-    spi.write(&[WREN])?;
-    write_counter.inc();
+    // At 3.3 V, clock speeds of up to 10 MHz are supported.
+    let mut eeprom = Eeprom25aa1024::open(Bus::Spi0, SlaveSelect::Ss0, 8_000_000)?;
+    eeprom.register_metrics(prometheus::default_registry())?;
 
-    // Use the WRITE instruction to select memory address 0 and write 5 bytes
-    // (1, 2, 3, 4, 5). Addresses are specified as 24-bit values, but the 7 most
-    // significant bits are ignored.
-    spi.write(&[WRITE, 0, 0, 0, 1, 2, 3, 4, 5])?;
-    write_counter.inc();
+    // RDID also releases the chip from deep power-down, should a previous run
+    // have left it there.
+    eeprom.check_identity()?;
 
-    // Read the STATUS register by writing the RDSR instruction, and then reading
-    // a single byte. Loop until the WIP bit is set to 0, indicating the write
-    // operation is completed. transfer_segments() will keep the Slave Select line
-    // active until both segments have been transferred.
-    let mut buffer = [0u8; 1];
-    loop {
-        spi.transfer_segments(&[
-            Segment::with_write(&[RDSR]),
-            Segment::with_read(&mut buffer),
-        ])?;
+    // Write 5 bytes (1, 2, 3, 4, 5) at address 0; the driver sets the write
+    // enable latch and waits for the write cycle to complete.
+    eeprom.write(0, &[1, 2, 3, 4, 5])?;
 
-        if buffer[0] & WIP == 0 {
-            break;
-        }
-    }
-
-    // Use the READ instruction to select memory address 0, specified as a 24-bit
-    // value, and then read 5 bytes.
     let mut buffer = [0u8; 5];
-    spi.transfer_segments(&[
-        Segment::with_write(&[READ, 0, 0, 0]),
-        Segment::with_read(&mut buffer),
-    ])?;
-    read_counter.inc();
+    eeprom.read(0, &mut buffer)?;
 
     println!("Bytes read: {:?}", buffer);
 
+    // Nothing else to store; save power until the next access wakes the chip.
+    eeprom.power_down()?;
+
     // Expose metrics (for demonstration, print to stdout)
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
@@ -72,4 +38,4 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("\nPrometheus metrics:\n{}", String::from_utf8(buffer).unwrap());
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,346 @@
+use std::error::Error;
+use std::fmt;
+
+/// Code shown in place of characters that cannot be mapped.
+pub const REPLACEMENT: u8 = b'?';
+
+/// Non-ASCII characters the controller's character ROM (the HD44780 A00
+/// table used by the PmodCLS) has a code for.
+///
+/// The ROM differs from ASCII at 0x5C, which shows a yen sign, and at 0x7E
+/// and 0x7F, which show arrows, so `\` and `~` have no code at all.
+const ROM: [(char, u8); 31] = [
+    ('¥', 0x5C),
+    ('→', 0x7E),
+    ('←', 0x7F),
+    ('·', 0xA5),
+    ('α', 0xE0),
+    ('ä', 0xE1),
+    ('β', 0xE2),
+    ('ß', 0xE2),
+    ('ε', 0xE3),
+    ('µ', 0xE4),
+    ('μ', 0xE4),
+    ('σ', 0xE5),
+    ('ρ', 0xE6),
+    ('√', 0xE8),
+    ('¢', 0xEC),
+    ('£', 0xED),
+    ('ñ', 0xEE),
+    ('ö', 0xEF),
+    ('θ', 0xF2),
+    ('∞', 0xF3),
+    ('Ω', 0xF4),
+    ('ü', 0xF5),
+    ('Σ', 0xF6),
+    ('π', 0xF7),
+    ('÷', 0xFD),
+    ('█', 0xFF),
+    ('°', 0xDF),
+    ('º', 0xDF),
+    ('−', b'-'),
+    ('\u{a0}', b' '),
+    ('\u{2009}', b' '),
+];
+
+/// Fallback spellings for characters the ROM lacks, tried after the ROM.
+/// Each spelling is itself encoded through the ROM table.
+const TRANSLITERATIONS: [(char, &str); 60] = [
+    ('À', "A"),
+    ('Á', "A"),
+    ('Â', "A"),
+    ('Ã', "A"),
+    ('Ä', "A"),
+    ('Å', "A"),
+    ('Æ', "AE"),
+    ('Ç', "C"),
+    ('È', "E"),
+    ('É', "E"),
+    ('Ê', "E"),
+    ('Ë', "E"),
+    ('Ì', "I"),
+    ('Í', "I"),
+    ('Î', "I"),
+    ('Ï', "I"),
+    ('Ñ', "N"),
+    ('Ò', "O"),
+    ('Ó', "O"),
+    ('Ô', "O"),
+    ('Õ', "O"),
+    ('Ö', "O"),
+    ('Ø', "O"),
+    ('Ù', "U"),
+    ('Ú', "U"),
+    ('Û', "U"),
+    ('Ü', "U"),
+    ('Ý', "Y"),
+    ('à', "a"),
+    ('á', "a"),
+    ('â', "a"),
+    ('ã', "a"),
+    ('å', "a"),
+    ('æ', "ae"),
+    ('ç', "c"),
+    ('è', "e"),
+    ('é', "e"),
+    ('ê', "e"),
+    ('ë', "e"),
+    ('ì', "i"),
+    ('í', "i"),
+    ('î', "i"),
+    ('ï', "i"),
+    ('ò', "o"),
+    ('ó', "o"),
+    ('ô', "o"),
+    ('õ', "o"),
+    ('ø', "o"),
+    ('ù', "u"),
+    ('ú', "u"),
+    ('û', "u"),
+    ('ý', "y"),
+    ('ÿ', "y"),
+    ('Œ', "OE"),
+    ('œ', "oe"),
+    ('×', "x"),
+    ('℃', "°C"),
+    ('€', "EUR"),
+    ('…', "..."),
+    ('²', "2"),
+];
+
+/// Punctuation that text editors like to substitute for plain ASCII.
+const PUNCTUATION: [(char, char); 7] = [
+    ('‘', '\''),
+    ('’', '\''),
+    ('“', '"'),
+    ('”', '"'),
+    ('–', '-'),
+    ('—', '-'),
+    ('\u{2022}', '\u{b7}'),
+];
+
+/// Text that contains characters the display cannot show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeError {
+    /// The unmappable characters, in order of appearance.
+    pub unmappable: Vec<char>,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no display character for {:?}", self.unmappable)
+    }
+}
+
+impl Error for EncodeError {}
+
+/// Maps Unicode text to codes of the display's character ROM.
+///
+/// Each character is looked up in the substitution table first, then in the
+/// ROM, then in a table of transliterations (`é` becomes `e`, `℃` becomes
+/// `°C`). Substitutions take precedence so a user character can replace a
+/// ROM glyph, e.g. a custom degree sign:
+///
+/// ```text
+/// let charset = Charset::new().with_substitution('°', 0);
+/// ```
+///
+/// Widths are counted in display cells, which is what a row is measured in;
+/// a transliteration can take more than one cell.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Charset {
+    substitutions: Vec<(char, u8)>,
+}
+
+impl Charset {
+    /// Creates a charset with the plain ROM mapping.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps `ch` to `code`, e.g. a user character slot (0-7).
+    pub fn with_substitution(mut self, ch: char, code: u8) -> Self {
+        self.substitute(ch, code);
+        self
+    }
+
+    /// Maps `ch` to `code`, replacing any earlier substitution for it.
+    pub fn substitute(&mut self, ch: char, code: u8) {
+        self.substitutions.retain(|&(c, _)| c != ch);
+        self.substitutions.push((ch, code));
+    }
+
+    /// Removes the substitution for `ch`, if any.
+    pub fn remove_substitution(&mut self, ch: char) {
+        self.substitutions.retain(|&(c, _)| c != ch);
+    }
+
+    /// Encodes `text`, failing if any character cannot be shown.
+    ///
+    /// # Errors
+    /// * Returns an `EncodeError` listing every unmappable character.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, EncodeError> {
+        let mut codes = Vec::with_capacity(text.len());
+        let mut unmappable = Vec::new();
+        for ch in text.chars() {
+            if !self.push_char(ch, &mut codes) {
+                unmappable.push(ch);
+            }
+        }
+        if unmappable.is_empty() { Ok(codes) } else { Err(EncodeError { unmappable }) }
+    }
+
+    /// Encodes `text`, showing `REPLACEMENT` for unmappable characters.
+    pub fn encode_lossy(&self, text: &str) -> Vec<u8> {
+        let mut codes = Vec::with_capacity(text.len());
+        for ch in text.chars() {
+            if !self.push_char(ch, &mut codes) {
+                codes.push(REPLACEMENT);
+            }
+        }
+        codes
+    }
+
+    /// Returns the number of display cells `encode_lossy` would fill.
+    pub fn width(&self, text: &str) -> usize {
+        text.chars().map(|ch| self.char_width(ch)).sum()
+    }
+
+    /// Returns the number of display cells a single character fills.
+    pub fn char_width(&self, ch: char) -> usize {
+        if self.lookup(ch).is_some() {
+            return 1;
+        }
+        match transliteration(ch) {
+            Some(spelling) => spelling.chars().count(),
+            None => 1,
+        }
+    }
+
+    /// Returns true if every character of `text` can be shown.
+    pub fn can_encode(&self, text: &str) -> bool {
+        text.chars().all(|ch| self.lookup(ch).is_some() || transliteration(ch).is_some())
+    }
+
+    /// Looks up a single-cell code, without transliteration.
+    fn lookup(&self, ch: char) -> Option<u8> {
+        if let Some(&(_, code)) = self.substitutions.iter().find(|&&(c, _)| c == ch) {
+            return Some(code);
+        }
+        rom_code(ch)
+    }
+
+    /// Appends the codes for `ch`, returning false if it has none.
+    fn push_char(&self, ch: char, codes: &mut Vec<u8>) -> bool {
+        if let Some(code) = self.lookup(ch) {
+            codes.push(code);
+            return true;
+        }
+        match transliteration(ch) {
+            Some(spelling) => {
+                codes.extend(spelling.chars().filter_map(|c| self.lookup(c)));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Returns the ROM code for `ch`, or `None` if the ROM has no such glyph.
+pub fn rom_code(ch: char) -> Option<u8> {
+    let ch = PUNCTUATION.iter().find(|&&(c, _)| c == ch).map_or(ch, |&(_, ascii)| ascii);
+    match ch {
+        ' '..='}' if ch != '\\' => Some(ch as u8),
+        _ => ROM.iter().find(|&&(c, _)| c == ch).map(|&(_, code)| code),
+    }
+}
+
+/// Returns the character a ROM code shows, or `None` for user characters,
+/// blank codes and the katakana block.
+pub fn rom_char(code: u8) -> Option<char> {
+    match code {
+        0x20..=0x7D if code != 0x5C => Some(char::from(code)),
+        // The first ROM entry for a code is its canonical character.
+        _ => ROM.iter().find(|&&(_, c)| c == code).map(|&(ch, _)| ch),
+    }
+}
+
+fn transliteration(ch: char) -> Option<&'static str> {
+    TRANSLITERATIONS.iter().find(|&&(c, _)| c == ch).map(|&(_, spelling)| spelling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_passes_through_except_backslash_and_tilde() {
+        let charset = Charset::new();
+        assert_eq!(charset.encode("Soil 42% [ok]").unwrap(), b"Soil 42% [ok]");
+        assert_eq!(charset.encode("a\\b~c"), Err(EncodeError { unmappable: vec!['\\', '~'] }));
+    }
+
+    #[test]
+    fn rom_glyphs_win_over_transliteration() {
+        let charset = Charset::new();
+        assert_eq!(charset.encode("21°C").unwrap(), [b'2', b'1', 0xDF, b'C']);
+        assert_eq!(charset.encode("ä→µ").unwrap(), [0xE1, 0x7E, 0xE4]);
+        assert_eq!(charset.encode("“Dry”—water").unwrap(), b"\"Dry\"-water");
+    }
+
+    #[test]
+    fn missing_letters_are_transliterated() {
+        let charset = Charset::new();
+        assert_eq!(charset.encode("Café Ærø").unwrap(), b"Cafe AEro");
+        assert_eq!(charset.encode("5€…").unwrap(), b"5EUR...");
+        assert_eq!(charset.encode("℃").unwrap(), [0xDF, b'C']);
+    }
+
+    #[test]
+    fn substitutions_take_precedence_everywhere() {
+        let mut charset = Charset::new().with_substitution('°', 0).with_substitution('~', 1);
+        assert_eq!(charset.encode("21°~").unwrap(), [b'2', b'1', 0, 1]);
+        // Transliterations are spelled through the same lookup.
+        assert_eq!(charset.encode("℃").unwrap(), [0, b'C']);
+
+        charset.substitute('°', 2);
+        assert_eq!(charset.encode("°").unwrap(), [2]);
+        charset.remove_substitution('°');
+        assert_eq!(charset.encode("°").unwrap(), [0xDF]);
+    }
+
+    #[test]
+    fn unmappable_characters_are_reported_or_replaced() {
+        let charset = Charset::new();
+        let err = charset.encode("ok 🌱 ☔").unwrap_err();
+        assert_eq!(err.unmappable, ['🌱', '☔']);
+        assert!(!charset.can_encode("ok 🌱"));
+        assert_eq!(charset.encode_lossy("ok 🌱 ☔"), b"ok ? ?");
+    }
+
+    #[test]
+    fn width_matches_the_lossy_encoding() {
+        let charset = Charset::new().with_substitution('€', 3);
+        let table_chars = ROM
+            .iter()
+            .map(|&(c, _)| c)
+            .chain(TRANSLITERATIONS.iter().map(|&(c, _)| c))
+            .chain(PUNCTUATION.iter().map(|&(c, _)| c));
+        for ch in table_chars.chain(['a', '\\', '🌱']) {
+            let text = ch.to_string();
+            assert_eq!(charset.width(&text), charset.encode_lossy(&text).len(), "{:?}", ch);
+        }
+        assert_eq!(charset.width("Ærø 5€ ℃"), 10);
+    }
+
+    #[test]
+    fn rom_char_reverses_rom_code() {
+        for code in (0x20..=0x7F).chain(0xA0..=0xFF) {
+            if let Some(ch) = rom_char(code) {
+                assert_eq!(rom_code(ch), Some(code), "{:#04X} {:?}", code, ch);
+            }
+        }
+        assert_eq!(rom_char(0x5C), Some('¥'));
+        assert_eq!(rom_char(0x03), None);
+    }
+}
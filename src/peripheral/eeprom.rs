@@ -0,0 +1,912 @@
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, trace};
+use prometheus::{Histogram, HistogramOpts, IntCounter, Opts, Registry};
+use rppal::spi::{Bus, Mode, Segment, SlaveSelect, Spi};
+
+// Instruction set.
+const WRITE: u8 = 0b0010; // Write data, starting at the selected address.
+const READ: u8 = 0b0011; // Read data, starting at the selected address.
+const RDSR: u8 = 0b0101; // Read the STATUS register.
+const WRSR: u8 = 0b0001; // Write the STATUS register.
+const WREN: u8 = 0b0110; // Set the write enable latch (enable write operations).
+const PE: u8 = 0x42; // Erase the page containing the selected address.
+const SE: u8 = 0xD8; // Erase the sector containing the selected address.
+const CE: u8 = 0xC7; // Erase the whole array.
+const RDID: u8 = 0xAB; // Release from deep power-down and read the electronic signature.
+const DPD: u8 = 0xB9; // Enter deep power-down.
+
+const WIP: u8 = 1; // Write-In-Process bit mask for the STATUS register.
+const WEL: u8 = 1 << 1; // Write Enable Latch bit mask for the STATUS register.
+const BP0: u8 = 1 << 2; // Block Protection bit masks for the STATUS register.
+const BP1: u8 = 1 << 3;
+const WPEN: u8 = 1 << 7; // Write-Protect Enable bit mask for the STATUS register.
+
+/// Size of the array in bytes (1 Mbit).
+pub const SIZE: u32 = 128 * 1024;
+
+/// Size of a write page in bytes.
+pub const PAGE_SIZE: u32 = 256;
+
+/// Size of an erase sector in bytes.
+pub const SECTOR_SIZE: u32 = 32 * 1024;
+
+/// Electronic signature returned by RDID.
+pub const SIGNATURE: u8 = 0x29;
+
+/// `device` label of the metrics of a driver created with `new`.
+pub const DEFAULT_DEVICE: &str = "eeprom";
+
+/// Highest SPI clock the 25AA1024 supports at 3.3 V.
+pub const MAX_CLOCK_SPEED: u32 = 10_000_000;
+
+/// Default time a write is given to complete before `write` gives up (the
+/// datasheet maximum write cycle is 6 ms).
+pub const WRITE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Time sector and chip erases are given to complete (datasheet maximum 10 ms).
+pub const ERASE_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Time the chip needs to leave deep power-down after RDID.
+const RELEASE_DELAY: Duration = Duration::from_micros(100);
+
+/// First pause between STATUS polls; it doubles up to `POLL_MAX_INTERVAL`.
+const POLL_INITIAL_INTERVAL: Duration = Duration::from_micros(50);
+const POLL_MAX_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Errors reported by the EEPROM driver.
+#[derive(Debug)]
+pub enum EepromError {
+    /// The address is not within the 128 KiB array.
+    AddressRange(u32),
+    /// The access starting at `addr` runs `len` bytes past the end of the array.
+    Overflow { addr: u32, len: usize },
+    /// The byte at this address read back differently from what was written.
+    Verify(u32),
+    /// The write starting at this address falls in a block-protected area.
+    Protected(u32),
+    /// The STATUS register ignored a write: WPEN is set and WP is held low.
+    StatusLocked,
+    /// RDID returned something other than `SIGNATURE`; 0x00 or 0xFF usually
+    /// means no chip is answering.
+    WrongSignature(u8),
+    /// The write-in-progress bit did not clear within `waited`. A `status`
+    /// of 0xFF usually means MISO is floating: the chip is absent or the bus
+    /// is miswired.
+    DeviceNotResponding { waited: Duration, status: u8 },
+    /// The SPI clock speed is zero or above `MAX_CLOCK_SPEED`.
+    ClockSpeed(u32),
+    /// The SPI bus could not be opened or failed to transfer.
+    Bus(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for EepromError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EepromError::AddressRange(a) => write!(f, "address {:#07X} is beyond the {} byte array", a, SIZE),
+            EepromError::Overflow { addr, len } => {
+                write!(f, "{} bytes at {:#07X} run past the end of the array", len, addr)
+            }
+            EepromError::Verify(a) => write!(f, "verification failed at address {:#07X}", a),
+            EepromError::Protected(a) => write!(f, "address {:#07X} is write-protected", a),
+            EepromError::StatusLocked => write!(f, "STATUS register is locked by WPEN and the WP pin"),
+            EepromError::WrongSignature(v) => {
+                write!(f, "electronic signature {:#04X} is not the 25AA1024's {:#04X}", v, SIGNATURE)
+            }
+            EepromError::DeviceNotResponding { waited, status } => {
+                write!(f, "device still busy after {:?} (status {:#04X})", waited, status)
+            }
+            EepromError::ClockSpeed(v) => write!(f, "clock speed {} Hz is not within 1-{} Hz", v, MAX_CLOCK_SPEED),
+            EepromError::Bus(e) => write!(f, "EEPROM bus error: {}", e),
+        }
+    }
+}
+
+impl Error for EepromError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EepromError::Bus(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Result type returned by the EEPROM driver.
+pub type Result<T> = std::result::Result<T, EepromError>;
+
+/// The SPI operations the EEPROM driver needs.
+///
+/// Both operations keep the chip selected for their whole duration, which is
+/// what delimits an instruction on the 25AA1024.
+pub trait EepromBus {
+    /// The error reported by the underlying bus when a transfer fails.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Writes `bytes` in a single transaction.
+    fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), Self::Error>;
+
+    /// Writes `command`, then reads `buffer.len()` bytes in the same transaction.
+    fn transfer(&mut self, command: &[u8], buffer: &mut [u8]) -> std::result::Result<(), Self::Error>;
+}
+
+impl EepromBus for Spi {
+    type Error = rppal::spi::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), Self::Error> {
+        Spi::write(self, bytes).map(|_| ())
+    }
+
+    fn transfer(&mut self, command: &[u8], buffer: &mut [u8]) -> std::result::Result<(), Self::Error> {
+        // transfer_segments() keeps Slave Select active until both segments
+        // have been transferred.
+        self.transfer_segments(&[Segment::with_write(command), Segment::with_read(buffer)])
+    }
+}
+
+/// An in-memory 25AA1024 that decodes the driver's instructions, so the
+/// driver and the stores built on it can be exercised without hardware.
+///
+/// Write cycles complete instantly. The array starts erased (all 0xFF).
+#[derive(Debug, Clone)]
+pub struct MockEepromBus {
+    memory: Vec<u8>,
+    status: u8,
+    powered_down: bool,
+    absent: bool,
+    tear_after: Option<usize>,
+}
+
+impl Default for MockEepromBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockEepromBus {
+    /// Creates a chip with an erased array.
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0xFF; SIZE as usize],
+            status: 0,
+            powered_down: false,
+            absent: false,
+            tear_after: None,
+        }
+    }
+
+    /// Creates a bus with no chip on it: writes go nowhere and every read
+    /// returns all ones, as a floating MISO line does.
+    pub fn absent() -> Self {
+        Self {
+            absent: true,
+            ..Self::new()
+        }
+    }
+
+    /// Returns the array contents.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Returns the array contents for tests to corrupt.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// Makes the next WRITE store only its first `bytes` data bytes, as if
+    /// power failed part way through.
+    pub fn tear_next_write(&mut self, bytes: usize) {
+        self.tear_after = Some(bytes);
+    }
+
+    /// Returns true if the chip is in deep power-down.
+    pub fn is_powered_down(&self) -> bool {
+        self.powered_down
+    }
+
+    fn address(bytes: &[u8]) -> u32 {
+        bytes[1..4].iter().fold(0, |a, &b| a << 8 | b as u32) % SIZE
+    }
+
+    fn protected(&self, addr: u32, len: usize) -> bool {
+        Status(self.status).protection().covers(addr, len)
+    }
+
+    /// Consumes the write enable latch, returning whether it was set.
+    fn take_wel(&mut self) -> bool {
+        let enabled = self.status & WEL != 0;
+        self.status &= !WEL;
+        enabled
+    }
+}
+
+impl EepromBus for MockEepromBus {
+    type Error = Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), Self::Error> {
+        if self.absent || self.powered_down || bytes.is_empty() {
+            return Ok(());
+        }
+        match bytes[0] {
+            WREN => self.status |= WEL,
+            WRITE if bytes.len() > 4 && self.take_wel() => {
+                let addr = Self::address(bytes);
+                let mut data = &bytes[4..];
+                if let Some(n) = self.tear_after.take() {
+                    data = &data[..n.min(data.len())];
+                }
+                if !self.protected(addr, 1) {
+                    // Like the chip, wrap around within the page.
+                    let page = addr - addr % PAGE_SIZE;
+                    for (i, &b) in data.iter().enumerate() {
+                        self.memory[(page + (addr + i as u32) % PAGE_SIZE) as usize] = b;
+                    }
+                }
+            }
+            WRSR if bytes.len() > 1 && self.take_wel() => {
+                self.status = self.status & !Status::WRITABLE | bytes[1] & Status::WRITABLE;
+            }
+            PE | SE if bytes.len() >= 4 && self.take_wel() => {
+                let size = if bytes[0] == PE { PAGE_SIZE } else { SECTOR_SIZE };
+                let addr = Self::address(bytes);
+                let start = addr - addr % size;
+                if !self.protected(start, size as usize) {
+                    self.memory[start as usize..(start + size) as usize].fill(0xFF);
+                }
+            }
+            CE if self.take_wel() && Status(self.status).protection() == Protection::None => {
+                self.memory.fill(0xFF);
+            }
+            DPD => self.powered_down = true,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn transfer(&mut self, command: &[u8], buffer: &mut [u8]) -> std::result::Result<(), Self::Error> {
+        if self.absent {
+            buffer.fill(0xFF);
+            return Ok(());
+        }
+        match command.first() {
+            Some(&RDID) => {
+                self.powered_down = false;
+                buffer.fill(SIGNATURE);
+            }
+            _ if self.powered_down => buffer.fill(0xFF),
+            Some(&READ) if command.len() >= 4 => {
+                let addr = Self::address(command);
+                for (i, b) in buffer.iter_mut().enumerate() {
+                    *b = self.memory[((addr + i as u32) % SIZE) as usize];
+                }
+            }
+            Some(&RDSR) => buffer.fill(self.status),
+            _ => buffer.fill(0xFF),
+        }
+        Ok(())
+    }
+}
+
+/// Contents of the STATUS register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status(pub u8);
+
+impl Status {
+    /// Bits `write_status` can change; the others are read-only.
+    pub const WRITABLE: u8 = BP0 | BP1 | WPEN;
+
+    /// Returns true while a write cycle is in progress.
+    pub fn write_in_progress(self) -> bool {
+        self.0 & WIP != 0
+    }
+
+    /// Returns true if the write enable latch is set.
+    pub fn write_enabled(self) -> bool {
+        self.0 & WEL != 0
+    }
+
+    /// Returns the protected area selected by BP1 and BP0.
+    pub fn protection(self) -> Protection {
+        match (self.0 & BP1 != 0, self.0 & BP0 != 0) {
+            (false, false) => Protection::None,
+            (false, true) => Protection::UpperQuarter,
+            (true, false) => Protection::UpperHalf,
+            (true, true) => Protection::All,
+        }
+    }
+
+    /// Returns true if the WP pin also guards the STATUS register (WPEN).
+    pub fn write_protect_enabled(self) -> bool {
+        self.0 & WPEN != 0
+    }
+
+    /// Returns the status with the block protection bits set for `protection`.
+    pub fn with_protection(self, protection: Protection) -> Self {
+        let bits = match protection {
+            Protection::None => 0,
+            Protection::UpperQuarter => BP0,
+            Protection::UpperHalf => BP1,
+            Protection::All => BP0 | BP1,
+        };
+        Status(self.0 & !(BP0 | BP1) | bits)
+    }
+
+    /// Returns the status with WPEN set or cleared.
+    pub fn with_write_protect_enabled(self, enabled: bool) -> Self {
+        Status(if enabled { self.0 | WPEN } else { self.0 & !WPEN })
+    }
+}
+
+/// Array blocks protected from writes by the BP1 and BP0 bits.
+///
+/// Protection always covers the top of the array, so data meant to be
+/// locked (calibration, device identity) belongs in the upper addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    None,
+    /// 0x18000-0x1FFFF.
+    UpperQuarter,
+    /// 0x10000-0x1FFFF.
+    UpperHalf,
+    /// The whole array.
+    All,
+}
+
+impl Protection {
+    /// Returns the protected address range.
+    pub fn range(self) -> Range<u32> {
+        match self {
+            Protection::None => SIZE..SIZE,
+            Protection::UpperQuarter => SIZE / 4 * 3..SIZE,
+            Protection::UpperHalf => SIZE / 2..SIZE,
+            Protection::All => 0..SIZE,
+        }
+    }
+
+    /// Returns true if any of `len` bytes starting at `addr` are protected.
+    pub fn covers(self, addr: u32, len: usize) -> bool {
+        len > 0 && addr as u64 + len as u64 > self.range().start as u64
+    }
+}
+
+/// Driver for the Microchip 25AA1024 1 Mbit SPI serial EEPROM.
+///
+/// Addresses are 24-bit on the wire, of which the driver accepts the
+/// 0..`SIZE` range. Writes are split into pages, set the write enable latch
+/// themselves and wait for each write cycle to finish before returning.
+pub struct Eeprom25aa1024<B: EepromBus> {
+    bus: B,
+    metrics: Metrics,
+    write_timeout: Duration,
+    verify: bool,
+    protection: Option<Protection>,
+    powered_down: bool,
+}
+
+impl Eeprom25aa1024<Spi> {
+    /// Opens the chip on an SPI bus in mode 0.
+    ///
+    /// # Errors
+    /// * Returns `ClockSpeed` if the clock is zero or above `MAX_CLOCK_SPEED`,
+    ///   or a bus error if the SPI device cannot be opened.
+    pub fn open(bus: Bus, slave_select: SlaveSelect, clock_speed: u32) -> Result<Self> {
+        if clock_speed == 0 || clock_speed > MAX_CLOCK_SPEED {
+            return Err(EepromError::ClockSpeed(clock_speed));
+        }
+        // The 25AA1024 clocks in data on the first rising edge of the clock
+        // signal (SPI mode 0).
+        let spi = Spi::new(bus, slave_select, clock_speed, Mode::Mode0).map_err(|e| {
+            error!("Failed to open {} {}: {:?}", bus, slave_select, e);
+            EepromError::Bus(Box::new(e))
+        })?;
+        Ok(Self::new(spi).with_device(&format!("spidev{}.{}", bus as u8, slave_select as u8)))
+    }
+}
+
+/// Operation counters of one chip, all labelled with its `device` name.
+struct Metrics {
+    writes: IntCounter,
+    reads: IntCounter,
+    polls: IntCounter,
+    write_latency: Histogram,
+}
+
+impl Metrics {
+    fn new(device: &str) -> Self {
+        let counter = |name: &str, help: &str| {
+            IntCounter::with_opts(Opts::new(name, help).const_label("device", device)).unwrap()
+        };
+        Self {
+            writes: counter("spi_write_total", "Total SPI write operations"),
+            reads: counter("spi_read_total", "Total SPI read operations"),
+            polls: counter("eeprom_wip_polls_total", "STATUS reads spent waiting for writes"),
+            write_latency: Histogram::with_opts(
+                HistogramOpts::new("eeprom_write_latency_seconds", "Time from WRITE until WIP cleared")
+                    .const_label("device", device)
+                    .buckets(vec![0.001, 0.002, 0.004, 0.006, 0.008, 0.010, 0.025, 0.1]),
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl<B: EepromBus> Eeprom25aa1024<B> {
+    /// Creates a driver on an already configured bus.
+    pub fn new(bus: B) -> Self {
+        Self {
+            bus,
+            metrics: Metrics::new(DEFAULT_DEVICE),
+            write_timeout: WRITE_TIMEOUT,
+            verify: false,
+            protection: None,
+            powered_down: false,
+        }
+    }
+
+    /// Names the chip in the `device` label of its metrics, so several chips
+    /// can share a registry. `open` uses the spidev name, e.g. `spidev0.0`.
+    ///
+    /// Replaces the metrics with fresh ones, so call it before registering.
+    pub fn with_device(mut self, device: &str) -> Self {
+        self.metrics = Metrics::new(device);
+        self
+    }
+
+    /// Registers the read, write, poll and latency metrics with a Prometheus
+    /// registry.
+    pub fn register_metrics(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.metrics.writes.clone()))?;
+        registry.register(Box::new(self.metrics.reads.clone()))?;
+        registry.register(Box::new(self.metrics.polls.clone()))?;
+        registry.register(Box::new(self.metrics.write_latency.clone()))
+    }
+
+    /// Sets how long a write may take before it fails with `DeviceNotResponding`.
+    pub fn set_write_timeout(&mut self, timeout: Duration) {
+        self.write_timeout = timeout;
+    }
+
+    /// Returns a reference to the underlying bus.
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Returns a mutable reference to the underlying bus.
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Consumes the driver and returns the underlying bus.
+    pub fn into_bus(self) -> B {
+        self.bus
+    }
+
+    /// Reads `buf.len()` bytes starting at `addr`.
+    ///
+    /// # Errors
+    /// * Returns `AddressRange` or `Overflow` if the range is outside the
+    ///   array, or a bus error if the transfer fails.
+    pub fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        check_range(addr, buf.len())?;
+        let command = with_address(READ, addr);
+        self.transfer(&command, buf, "read")?;
+        self.metrics.reads.inc();
+        Ok(())
+    }
+
+    /// Turns read-back verification of every written page on or off.
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Writes `data` of any length starting at `addr`.
+    ///
+    /// The chip wraps writes around within a 256-byte page, so the data is
+    /// split at page boundaries and each page is written, and optionally
+    /// verified, as its own write cycle.
+    ///
+    /// # Errors
+    /// * Returns `AddressRange` or `Overflow` if the range is outside the
+    ///   array, `Protected` if it touches a block-protected area,
+    ///   `DeviceNotResponding` if a write does not complete, `Verify` if a
+    ///   page reads back differently, or a bus error if a transfer fails.
+    ///   Pages before the failing one have been written.
+    pub fn write(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        check_range(addr, data.len())?;
+        // The chip silently ignores writes to protected blocks.
+        if self.protection()?.covers(addr, data.len()) {
+            return Err(EepromError::Protected(addr));
+        }
+        let mut addr = addr;
+        let mut rest = data;
+        while !rest.is_empty() {
+            let room = (PAGE_SIZE - addr % PAGE_SIZE) as usize;
+            let (page, tail) = rest.split_at(room.min(rest.len()));
+            self.write_page(addr, page)?;
+            if self.verify {
+                self.verify_page(addr, page)?;
+            }
+            addr += page.len() as u32;
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Writes data that lies within one page and waits for the write cycle.
+    fn write_page(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        // The write enable latch is reset after every successful write.
+        self.send(&[WREN], "wren")?;
+        let mut command = with_address(WRITE, addr).to_vec();
+        command.extend_from_slice(data);
+        let start = Instant::now();
+        self.send(&command, "write")?;
+        self.metrics.writes.inc();
+        self.wait_ready(self.write_timeout)?;
+        self.metrics.write_latency.observe(start.elapsed().as_secs_f64());
+        Ok(())
+    }
+
+    fn verify_page(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        let mut readback = vec![0u8; data.len()];
+        self.read(addr, &mut readback)?;
+        match data.iter().zip(&readback).position(|(a, b)| a != b) {
+            Some(offset) => Err(EepromError::Verify(addr + offset as u32)),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the STATUS register.
+    ///
+    /// # Errors
+    /// * Returns a bus error if the transfer fails.
+    pub fn status(&mut self) -> Result<Status> {
+        let mut buffer = [0u8; 1];
+        self.transfer(&[RDSR], &mut buffer, "rdsr")?;
+        Ok(Status(buffer[0]))
+    }
+
+    /// Writes the STATUS register. Only the `Status::WRITABLE` bits are sent.
+    ///
+    /// While WPEN is set and the WP pin is held low the chip ignores this.
+    ///
+    /// # Errors
+    /// * Returns `DeviceNotResponding` if the write does not complete, or a
+    ///   bus error if a transfer fails.
+    pub fn write_status(&mut self, status: Status) -> Result<()> {
+        self.send(&[WREN], "wren")?;
+        self.send(&[WRSR, status.0 & Status::WRITABLE], "wrsr")?;
+        self.protection = None;
+        self.wait_ready(self.write_timeout)
+    }
+
+    /// Returns the block-protected area, reading STATUS on first use.
+    ///
+    /// STATUS is only decoded once the chip reports no write in progress.
+    /// With no chip on the bus it reads 0xFF, WIP included, so an absent chip
+    /// is reported as `DeviceNotResponding` rather than as fully protected.
+    ///
+    /// # Errors
+    /// * Returns `DeviceNotResponding` if the chip stays busy, or a bus error
+    ///   if a transfer fails.
+    pub fn protection(&mut self) -> Result<Protection> {
+        if let Some(protection) = self.protection {
+            return Ok(protection);
+        }
+        let protection = self.poll_ready(self.write_timeout)?.protection();
+        self.protection = Some(protection);
+        Ok(protection)
+    }
+
+    /// Protects an area of the array against writes, keeping WPEN as it is.
+    ///
+    /// # Errors
+    /// * Returns `StatusLocked` if the chip did not take the new setting,
+    ///   `DeviceNotResponding` if the chip is absent or the write does not
+    ///   complete, or a bus error if a transfer fails.
+    pub fn set_protection(&mut self, protection: Protection) -> Result<()> {
+        let status = self.poll_ready(self.write_timeout)?;
+        self.write_status(status.with_protection(protection))?;
+        let now = self.protection()?;
+        if now != protection {
+            return Err(EepromError::StatusLocked);
+        }
+        info!("EEPROM protection set to {:?}", protection);
+        Ok(())
+    }
+
+    /// Polls the STATUS register until no write is in progress.
+    ///
+    /// The pause between polls starts short, as most write cycles finish in a
+    /// few milliseconds, and backs off so an absent chip does not keep the CPU
+    /// spinning until the timeout.
+    ///
+    /// # Errors
+    /// * Returns `DeviceNotResponding` if the write is still in progress after
+    ///   `timeout`, or a bus error if a transfer fails.
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        self.poll_ready(timeout).map(|_| ())
+    }
+
+    /// Polls like `wait_ready` and returns the first STATUS without WIP set.
+    fn poll_ready(&mut self, timeout: Duration) -> Result<Status> {
+        let start = Instant::now();
+        let mut interval = POLL_INITIAL_INTERVAL;
+        loop {
+            let status = self.status()?;
+            self.metrics.polls.inc();
+            if !status.write_in_progress() {
+                return Ok(status);
+            }
+            let waited = start.elapsed();
+            if waited >= timeout {
+                error!("EEPROM not responding after {:?}, status {:#04X}", waited, status.0);
+                return Err(EepromError::DeviceNotResponding { waited, status: status.0 });
+            }
+            thread::sleep(interval.min(timeout - waited));
+            interval = (interval * 2).min(POLL_MAX_INTERVAL);
+        }
+    }
+
+    /// Erases (sets to 0xFF) the page containing `addr`.
+    ///
+    /// # Errors
+    /// * Returns `AddressRange` for an address beyond the array, `Protected`
+    ///   if the page is block-protected, `DeviceNotResponding` if the erase
+    ///   does not complete, or a bus error if a transfer fails.
+    pub fn erase_page(&mut self, addr: u32) -> Result<()> {
+        let page = addr - addr % PAGE_SIZE;
+        self.erase(PE, page, PAGE_SIZE, self.write_timeout, "page erase")
+    }
+
+    /// Erases the 32 KiB sector containing `addr`.
+    ///
+    /// # Errors
+    /// * Returns `AddressRange` for an address beyond the array, `Protected`
+    ///   if the sector is block-protected, `DeviceNotResponding` if the erase
+    ///   does not complete, or a bus error if a transfer fails.
+    pub fn erase_sector(&mut self, addr: u32) -> Result<()> {
+        let sector = addr - addr % SECTOR_SIZE;
+        self.erase(SE, sector, SECTOR_SIZE, ERASE_TIMEOUT, "sector erase")
+    }
+
+    /// Erases the whole array. The chip refuses while any block is protected.
+    ///
+    /// # Errors
+    /// * Returns `Protected` if any block is protected, `DeviceNotResponding`
+    ///   if the erase does not complete, or a bus error if a transfer fails.
+    pub fn erase_chip(&mut self) -> Result<()> {
+        let protection = self.protection()?;
+        if protection != Protection::None {
+            return Err(EepromError::Protected(protection.range().start));
+        }
+        self.send(&[WREN], "wren")?;
+        self.send(&[CE], "chip erase")?;
+        self.wait_ready(ERASE_TIMEOUT)?;
+        info!("EEPROM erased");
+        Ok(())
+    }
+
+    /// Puts the chip into deep power-down, its lowest-current state, e.g.
+    /// between logging bursts. The next operation wakes it automatically.
+    ///
+    /// # Errors
+    /// * Returns a bus error if the transfer fails.
+    pub fn power_down(&mut self) -> Result<()> {
+        if self.powered_down {
+            return Ok(());
+        }
+        self.send(&[DPD], "deep power-down")?;
+        self.powered_down = true;
+        Ok(())
+    }
+
+    /// Returns true if the chip was put into deep power-down and not woken.
+    pub fn is_powered_down(&self) -> bool {
+        self.powered_down
+    }
+
+    /// Releases the chip from deep power-down and returns its electronic
+    /// signature (`SIGNATURE` for a 25AA1024).
+    ///
+    /// # Errors
+    /// * Returns a bus error if the transfer fails.
+    pub fn read_id(&mut self) -> Result<u8> {
+        // RDID is followed by a dummy 24-bit address.
+        let mut signature = [0u8; 1];
+        self.bus_transfer(&with_address(RDID, 0), &mut signature, "rdid")?;
+        thread::sleep(RELEASE_DELAY);
+        self.powered_down = false;
+        Ok(signature[0])
+    }
+
+    /// Checks that a 25AA1024 answers on the bus, e.g. at start-up.
+    ///
+    /// # Errors
+    /// * Returns `WrongSignature` if the chip is absent or a different part,
+    ///   or a bus error if the transfer fails.
+    pub fn check_identity(&mut self) -> Result<()> {
+        match self.read_id()? {
+            SIGNATURE => Ok(()),
+            other => {
+                error!("EEPROM signature {:#04X}, expected {:#04X}", other, SIGNATURE);
+                Err(EepromError::WrongSignature(other))
+            }
+        }
+    }
+
+    fn erase(&mut self, instruction: u8, addr: u32, len: u32, timeout: Duration, context: &str) -> Result<()> {
+        check_range(addr, 1)?;
+        if self.protection()?.covers(addr, len as usize) {
+            return Err(EepromError::Protected(addr));
+        }
+        self.send(&[WREN], "wren")?;
+        self.send(&with_address(instruction, addr), context)?;
+        self.wait_ready(timeout)
+    }
+
+    /// Wakes the chip first if it is in deep power-down.
+    fn wake(&mut self) -> Result<()> {
+        if self.powered_down {
+            self.read_id()?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, bytes: &[u8], context: &str) -> Result<()> {
+        self.wake()?;
+        trace!("{} command: {:?}", context, bytes);
+        self.bus.write(bytes).map_err(|e| {
+            error!("EEPROM write failed in {}: {:?}", context, e);
+            EepromError::Bus(Box::new(e))
+        })
+    }
+
+    fn transfer(&mut self, command: &[u8], buffer: &mut [u8], context: &str) -> Result<()> {
+        self.wake()?;
+        self.bus_transfer(command, buffer, context)
+    }
+
+    fn bus_transfer(&mut self, command: &[u8], buffer: &mut [u8], context: &str) -> Result<()> {
+        trace!("{} command: {:?}", context, command);
+        self.bus.transfer(command, buffer).map_err(|e| {
+            error!("EEPROM transfer failed in {}: {:?}", context, e);
+            EepromError::Bus(Box::new(e))
+        })
+    }
+}
+
+/// Checks that `len` bytes starting at `addr` lie within the array.
+fn check_range(addr: u32, len: usize) -> Result<()> {
+    if addr >= SIZE {
+        return Err(EepromError::AddressRange(addr));
+    }
+    if addr as u64 + len as u64 > SIZE as u64 {
+        return Err(EepromError::Overflow { addr, len });
+    }
+    Ok(())
+}
+
+/// Builds an instruction followed by its 24-bit address, most significant
+/// byte first.
+fn with_address(instruction: u8, addr: u32) -> [u8; 4] {
+    [instruction, (addr >> 16) as u8, (addr >> 8) as u8, addr as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eeprom() -> Eeprom25aa1024<MockEepromBus> {
+        Eeprom25aa1024::new(MockEepromBus::new())
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn write_is_split_at_page_boundaries() {
+        let mut eeprom = eeprom();
+        let data = pattern(1000);
+        eeprom.write(200, &data).unwrap();
+
+        let mut readback = vec![0; data.len()];
+        eeprom.read(200, &mut readback).unwrap();
+        assert_eq!(readback, data);
+        // Nothing wrapped around to the start of a page.
+        let memory = eeprom.bus().memory();
+        assert!(memory[..200].iter().all(|&b| b == 0xFF));
+        assert!(memory[1200..1300].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn write_ending_at_the_last_byte_fits() {
+        let mut eeprom = eeprom();
+        eeprom.write(SIZE - 3, &[1, 2, 3]).unwrap();
+        assert_eq!(eeprom.bus().memory()[SIZE as usize - 3..], [1, 2, 3]);
+    }
+
+    #[test]
+    fn out_of_range_accesses_are_rejected() {
+        let mut eeprom = eeprom();
+        assert!(matches!(eeprom.write(SIZE, &[0]), Err(EepromError::AddressRange(SIZE))));
+        assert!(matches!(
+            eeprom.write(SIZE - 2, &[0; 4]),
+            Err(EepromError::Overflow { addr, len: 4 }) if addr == SIZE - 2
+        ));
+        let mut buf = [0; 2];
+        assert!(matches!(eeprom.read(SIZE - 1, &mut buf), Err(EepromError::Overflow { .. })));
+    }
+
+    #[test]
+    fn verify_reports_the_first_byte_that_did_not_stick() {
+        let mut eeprom = eeprom();
+        eeprom.set_verify(true);
+        eeprom.write(0, &pattern(32)).unwrap();
+        eeprom.bus_mut().tear_next_write(10);
+        assert!(matches!(eeprom.write(0x300, &pattern(32)), Err(EepromError::Verify(0x30A))));
+    }
+
+    #[test]
+    fn absent_chip_is_not_mistaken_for_a_protected_one() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::absent());
+        for _ in 0..2 {
+            let err = eeprom.write(0, &[1]).unwrap_err();
+            assert!(matches!(err, EepromError::DeviceNotResponding { status: 0xFF, .. }), "{}", err);
+        }
+        assert!(matches!(eeprom.erase_chip(), Err(EepromError::DeviceNotResponding { .. })));
+        assert!(matches!(eeprom.check_identity(), Err(EepromError::WrongSignature(0xFF))));
+    }
+
+    #[test]
+    fn protected_blocks_reject_writes_and_erases() {
+        let mut eeprom = eeprom();
+        eeprom.set_protection(Protection::UpperQuarter).unwrap();
+        assert_eq!(eeprom.status().unwrap().protection(), Protection::UpperQuarter);
+        eeprom.write(0x17FFE, &[1, 2]).unwrap();
+        assert!(matches!(eeprom.write(0x17FFF, &[1, 2]), Err(EepromError::Protected(0x17FFF))));
+        assert!(matches!(eeprom.erase_sector(0x18000), Err(EepromError::Protected(0x18000))));
+        assert!(matches!(eeprom.erase_chip(), Err(EepromError::Protected(0x18000))));
+
+        eeprom.set_protection(Protection::None).unwrap();
+        eeprom.write(0x1FFFF, &[7]).unwrap();
+        eeprom.erase_page(0x1FF00).unwrap();
+        assert_eq!(eeprom.bus().memory()[0x1FFFF], 0xFF);
+        assert_eq!(eeprom.bus().memory()[0x17FFE], 1);
+    }
+
+    #[test]
+    fn operations_wake_the_chip_from_power_down() {
+        let mut eeprom = eeprom();
+        eeprom.check_identity().unwrap();
+        eeprom.power_down().unwrap();
+        assert!(eeprom.bus().is_powered_down());
+        eeprom.write(0x40, b"awake").unwrap();
+        assert!(!eeprom.is_powered_down());
+        assert_eq!(&eeprom.bus().memory()[0x40..0x45], b"awake");
+    }
+
+    #[test]
+    fn device_label_lets_several_chips_share_a_registry() {
+        let registry = Registry::new();
+        let mut first = eeprom().with_device("spidev0.0");
+        let second = eeprom().with_device("spidev0.1");
+        first.register_metrics(&registry).unwrap();
+        second.register_metrics(&registry).unwrap();
+        assert!(eeprom().with_device("spidev0.0").register_metrics(&registry).is_err());
+
+        first.read(0, &mut [0; 4]).unwrap();
+        let reads = registry.gather().into_iter().find(|f| f.get_name() == "spi_read_total").unwrap();
+        let counts: Vec<_> = reads
+            .get_metric()
+            .iter()
+            .map(|m| (m.get_label()[0].get_value().to_string(), m.get_counter().get_value()))
+            .collect();
+        assert_eq!(counts, [("spidev0.0".to_string(), 1.0), ("spidev0.1".to_string(), 0.0)]);
+    }
+}
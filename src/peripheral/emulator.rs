@@ -0,0 +1,400 @@
+use std::convert::Infallible;
+use std::fmt;
+
+use log::trace;
+
+use super::charset::rom_char;
+use super::lcds::{
+    BRACKET, CURSOR_MODE_CMD, CURSOR_POS_CMD, CURSOR_RSTR_CMD, CURSOR_SAVE_CMD, CursorMode, DEF_CHAR_CMD,
+    DISP_CLR_CMD, DISP_EN_CMD, DISP_MODE_CMD, ERASE_FIELD_CMD, ERASE_INLINE_CMD, ESC, LSCROLL_CMD, PRG_CHAR_CMD,
+    RSCROLL_CMD, RST_CMD,
+};
+use super::transport::Transport;
+
+/// Number of rows on the PmodCLS.
+pub const ROWS: usize = 2;
+
+/// Number of character cells per row in the controller's display RAM.
+pub const RAM_COLS: usize = 40;
+
+/// Number of user-definable characters.
+pub const USER_CHARS: usize = 8;
+
+#[derive(Debug, Clone)]
+enum ParseState {
+    Text,
+    Escape,
+    Params(Vec<u8>),
+}
+
+/// A software model of the PmodCLS.
+///
+/// The emulator parses the escape sequences the driver emits and keeps the
+/// display RAM, cursor and mode state the real controller would. Use it as a
+/// [`Transport`] to assert on the visible screen, or print it to get a
+/// screenshot of the panel.
+#[derive(Debug, Clone)]
+pub struct LcdsEmulator {
+    visible_cols: usize,
+    ram: [[u8; RAM_COLS]; ROWS],
+    row: usize,
+    col: usize,
+    saved_pos: (usize, usize),
+    offset: usize,
+    wrap_16: bool,
+    display_on: bool,
+    backlight_on: bool,
+    cursor_mode: CursorMode,
+    defined_chars: [[u8; 8]; USER_CHARS],
+    user_chars: [[u8; 8]; USER_CHARS],
+    state: ParseState,
+}
+
+impl LcdsEmulator {
+    /// Creates an emulator for a panel showing `visible_cols` columns.
+    pub fn new(visible_cols: usize) -> Self {
+        Self {
+            visible_cols: visible_cols.clamp(1, RAM_COLS),
+            ram: [[b' '; RAM_COLS]; ROWS],
+            row: 0,
+            col: 0,
+            saved_pos: (0, 0),
+            offset: 0,
+            wrap_16: false,
+            display_on: true,
+            backlight_on: true,
+            cursor_mode: CursorMode::Off,
+            defined_chars: [[0; 8]; USER_CHARS],
+            user_chars: [[0; 8]; USER_CHARS],
+            state: ParseState::Text,
+        }
+    }
+
+    /// Creates an emulator for a 2x16 panel (the PmodCLS).
+    pub fn new_2x16() -> Self {
+        Self::new(16)
+    }
+
+    /// Creates an emulator for a 2x40 panel.
+    pub fn new_2x40() -> Self {
+        Self::new(40)
+    }
+
+    /// Returns the number of visible columns.
+    pub fn visible_cols(&self) -> usize {
+        self.visible_cols
+    }
+
+    /// Returns the raw byte stored at a display RAM position.
+    pub fn cell(&self, row: usize, col: usize) -> u8 {
+        self.ram[row][col]
+    }
+
+    /// Returns a full 40-character display RAM row as text.
+    pub fn ram_line(&self, row: usize) -> String {
+        self.ram[row].iter().map(|&b| Self::glyph(b)).collect()
+    }
+
+    /// Returns the visible part of a row, taking the scroll offset into account.
+    pub fn line(&self, row: usize) -> String {
+        (0..self.visible_cols)
+            .map(|i| Self::glyph(self.ram[row][(self.offset + i) % RAM_COLS]))
+            .collect()
+    }
+
+    /// Returns every visible row.
+    pub fn screen(&self) -> Vec<String> {
+        (0..ROWS).map(|row| self.line(row)).collect()
+    }
+
+    /// Returns the cursor position as (row, column).
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Returns the current cursor display mode.
+    pub fn cursor_mode(&self) -> CursorMode {
+        self.cursor_mode
+    }
+
+    /// Returns how many columns the display is shifted left.
+    pub fn scroll_offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns true if lines wrap at 16 characters rather than 40.
+    pub fn wraps_at_16(&self) -> bool {
+        self.wrap_16
+    }
+
+    /// Returns true if the display is enabled.
+    pub fn display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Returns true if the backlight is enabled.
+    pub fn backlight_on(&self) -> bool {
+        self.backlight_on
+    }
+
+    /// Returns the row data of a user character as defined with `d`, whether
+    /// or not it has been programmed into the LCD yet.
+    pub fn defined_char(&self, pos: usize) -> [u8; 8] {
+        self.defined_chars[pos]
+    }
+
+    /// Returns the row data of a user character as programmed into the LCD
+    /// with `p`.
+    pub fn user_char(&self, pos: usize) -> [u8; 8] {
+        self.user_chars[pos]
+    }
+
+    /// Feeds raw bytes to the emulator, exactly as a transport would.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.feed_byte(b);
+        }
+    }
+
+    fn glyph(b: u8) -> char {
+        match b {
+            // User-defined characters have no text form.
+            0..=7 => '#',
+            _ => rom_char(b).unwrap_or('?'),
+        }
+    }
+
+    fn feed_byte(&mut self, b: u8) {
+        let state = std::mem::replace(&mut self.state, ParseState::Text);
+        self.state = match state {
+            ParseState::Text if b == ESC => ParseState::Escape,
+            ParseState::Text => {
+                self.put(b);
+                ParseState::Text
+            }
+            ParseState::Escape if b == BRACKET => ParseState::Params(Vec::new()),
+            ParseState::Escape => {
+                // A lone ESC is not a command; show what followed it.
+                self.put(b);
+                ParseState::Text
+            }
+            ParseState::Params(mut params) => {
+                if Self::is_param_byte(&params, b) {
+                    params.push(b);
+                    ParseState::Params(params)
+                } else {
+                    self.execute(b, &params);
+                    ParseState::Text
+                }
+            }
+        }
+    }
+
+    fn is_param_byte(params: &[u8], b: u8) -> bool {
+        let current = params.rsplit(|&p| p == b';').next().unwrap_or(&[]);
+        let in_hex = current.len() >= 2 && current[0] == b'0' && matches!(current[1], b'x' | b'X');
+        match b {
+            b'0'..=b'9' | b';' => true,
+            b'x' | b'X' => current == b"0",
+            b'A'..=b'F' | b'a'..=b'f' => in_hex && current.len() < 4,
+            _ => false,
+        }
+    }
+
+    fn parse_params(params: &[u8]) -> Vec<usize> {
+        params
+            .split(|&p| p == b';')
+            .map(|p| {
+                let text = String::from_utf8_lossy(p);
+                match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                    Some(hex) => usize::from_str_radix(hex, 16).unwrap_or(0),
+                    None => text.parse().unwrap_or(0),
+                }
+            })
+            .collect()
+    }
+
+    fn execute(&mut self, cmd: u8, raw: &[u8]) {
+        let params = Self::parse_params(raw);
+        let first = params.first().copied().unwrap_or(0);
+        trace!("emulator command {:?} with {:?}", char::from(cmd), params);
+        match cmd {
+            CURSOR_POS_CMD => {
+                let row = first;
+                let col = params.get(1).copied().unwrap_or(0);
+                if row < ROWS && col < RAM_COLS {
+                    self.row = row;
+                    self.col = col;
+                }
+            }
+            CURSOR_SAVE_CMD => self.saved_pos = (self.row, self.col),
+            CURSOR_RSTR_CMD => (self.row, self.col) = self.saved_pos,
+            DISP_CLR_CMD => {
+                self.ram = [[b' '; RAM_COLS]; ROWS];
+                self.row = 0;
+                self.col = 0;
+                self.offset = 0;
+            }
+            ERASE_INLINE_CMD => {
+                let line = &mut self.ram[self.row];
+                match first {
+                    0 => line[self.col..].fill(b' '),
+                    1 => line[..=self.col].fill(b' '),
+                    2 => line.fill(b' '),
+                    _ => {}
+                }
+            }
+            ERASE_FIELD_CMD => {
+                let end = (self.col + first).min(RAM_COLS);
+                self.ram[self.row][self.col..end].fill(b' ');
+            }
+            LSCROLL_CMD => self.offset = (self.offset + first) % RAM_COLS,
+            RSCROLL_CMD => self.offset = (self.offset + RAM_COLS - first % RAM_COLS) % RAM_COLS,
+            RST_CMD => *self = Self::new(self.visible_cols),
+            DISP_EN_CMD => {
+                self.display_on = first & 1 != 0;
+                self.backlight_on = first & 2 != 0;
+            }
+            DISP_MODE_CMD => self.wrap_16 = first == 0,
+            CURSOR_MODE_CMD => {
+                self.cursor_mode = match first {
+                    0 => CursorMode::Off,
+                    1 => CursorMode::On,
+                    _ => CursorMode::Blink,
+                }
+            }
+            DEF_CHAR_CMD => {
+                // Eight row values followed by the character position. The
+                // definition only reaches the LCD once the table is programmed.
+                if let Some((&pos, rows)) = params.split_last()
+                    && pos < USER_CHARS
+                {
+                    for (dst, &src) in self.defined_chars[pos].iter_mut().zip(rows) {
+                        *dst = src as u8;
+                    }
+                }
+            }
+            // The parameter picks the controller's table slot; the emulator
+            // only models the table being defined.
+            PRG_CHAR_CMD => self.user_chars = self.defined_chars,
+            // Table programming and EEPROM persistence do not change what is shown.
+            _ => {}
+        }
+    }
+
+    fn put(&mut self, b: u8) {
+        self.ram[self.row][self.col] = b;
+        self.col += 1;
+        let wrap = if self.wrap_16 { 16 } else { RAM_COLS };
+        if self.col >= wrap {
+            self.col = 0;
+            self.row = (self.row + 1) % ROWS;
+        }
+    }
+}
+
+impl Default for LcdsEmulator {
+    fn default() -> Self {
+        Self::new_2x16()
+    }
+}
+
+impl Transport for LcdsEmulator {
+    type Error = Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.feed(bytes);
+        Ok(())
+    }
+}
+
+impl fmt::Display for LcdsEmulator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let border = "-".repeat(self.visible_cols);
+        writeln!(f, "+{}+", border)?;
+        for row in 0..ROWS {
+            let text = if self.display_on {
+                self.line(row)
+            } else {
+                " ".repeat(self.visible_cols)
+            };
+            writeln!(f, "|{}|", text)?;
+        }
+        write!(f, "+{}+", border)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::lcds::{Lcds, LcdsError};
+
+    fn lcds() -> Lcds<LcdsEmulator> {
+        Lcds::new(LcdsEmulator::new_2x16())
+    }
+
+    #[test]
+    fn write_string_at_pos_fills_cells() {
+        let mut lcds = lcds();
+        lcds.write_string_at_pos(0, 0, "Hello").unwrap();
+        lcds.write_string_at_pos(1, 3, "world").unwrap();
+        let emu = lcds.transport();
+        assert_eq!(emu.screen(), ["Hello           ", "   world        "]);
+        assert_eq!(emu.cursor(), (1, 8));
+    }
+
+    #[test]
+    fn display_scroll_shifts_the_window() {
+        let mut lcds = lcds();
+        lcds.write_string_at_pos(0, 0, "0123456789abcdefghij").unwrap();
+        lcds.display_scroll(false, 4).unwrap();
+        assert_eq!(lcds.transport().line(0), "456789abcdefghij");
+        lcds.display_scroll(true, 2).unwrap();
+        assert_eq!(lcds.transport().line(0), "23456789abcdefgh");
+        assert_eq!(lcds.transport().scroll_offset(), 2);
+    }
+
+    #[test]
+    fn erase_in_line_clears_around_the_cursor() {
+        let mut lcds = lcds();
+        for (param, expected) in [(0, "ABCD            "), (1, "     FGHIJ      "), (2, "                ")] {
+            lcds.write_string_at_pos(0, 0, "ABCDEFGHIJ").unwrap();
+            lcds.set_pos(0, 4).unwrap();
+            lcds.erase_in_line(param).unwrap();
+            assert_eq!(lcds.transport().line(0), expected, "erase option {}", param);
+        }
+        assert!(matches!(lcds.erase_in_line(3), Err(LcdsError::EraseOptions(3))));
+    }
+
+    #[test]
+    fn erase_chars_takes_multi_digit_counts() {
+        let mut lcds = lcds();
+        lcds.write_string_at_pos(0, 0, "ABCDEFGHIJKLMNOP").unwrap();
+        lcds.set_pos(0, 2).unwrap();
+        lcds.erase_chars(12).unwrap();
+        assert_eq!(lcds.transport().line(0), "AB            OP");
+    }
+
+    #[test]
+    fn user_chars_show_once_programmed() {
+        let rows = [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F];
+        let mut emu = LcdsEmulator::new_2x16();
+        emu.feed(b"\x1b[0x1F;0x11;0x11;0x11;0x11;0x11;0x11;0x1F;2d");
+        assert_eq!(emu.defined_char(2), rows);
+        assert_eq!(emu.user_char(2), [0; 8]);
+        emu.feed(b"\x1b[3p");
+        assert_eq!(emu.user_char(2), rows);
+
+        let mut lcds = lcds();
+        lcds.define_user_char(&rows, 5).unwrap();
+        assert_eq!(lcds.transport().user_char(5), rows);
+    }
+
+    #[test]
+    fn display_renders_a_screenshot() {
+        let mut lcds = lcds();
+        lcds.write_string_at_pos(0, 0, "Temp 21.5\u{b0}C").unwrap();
+        let expected = "+----------------+\n|Temp 21.5\u{b0}C     |\n|                |\n+----------------+";
+        assert_eq!(lcds.transport().to_string(), expected);
+    }
+}
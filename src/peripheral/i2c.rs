@@ -0,0 +1,167 @@
+use std::convert::Infallible;
+use std::error::Error;
+
+use rppal::i2c::I2c;
+
+use super::transport::Transport;
+
+/// Default TWI address of the PmodCLS (MD2..MD0 = 1,0,0).
+pub const TWI_DEFAULT_ADDR: u16 = 0x48;
+
+/// The subset of an I2C bus the [`I2cTransport`] needs.
+///
+/// Implemented for rppal's [`I2c`], and for [`MockI2cBus`] so the transport
+/// can be exercised without hardware.
+pub trait I2cBus {
+    /// The error reported by the bus.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Selects the slave device subsequent writes are addressed to.
+    fn set_slave_address(&mut self, address: u16) -> Result<(), Self::Error>;
+
+    /// Writes `bytes` to the selected slave in a single transaction.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl I2cBus for I2c {
+    type Error = rppal::i2c::Error;
+
+    fn set_slave_address(&mut self, address: u16) -> Result<(), Self::Error> {
+        I2c::set_slave_address(self, address)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        I2c::write(self, bytes).map(|_| ())
+    }
+}
+
+/// Drives the PmodCLS over TWI (I2C) at a configurable slave address.
+#[derive(Debug)]
+pub struct I2cTransport<B: I2cBus = I2c> {
+    bus: B,
+    address: u16,
+}
+
+impl I2cTransport<I2c> {
+    /// Opens the given I2C bus (e.g., 1 for the Pi's GPIO 2/3 header pins)
+    /// and addresses the display at `address`.
+    pub fn open(bus: u8, address: u16) -> Result<Self, rppal::i2c::Error> {
+        Self::new(I2c::with_bus(bus)?, address)
+    }
+}
+
+impl<B: I2cBus> I2cTransport<B> {
+    /// Wraps an already opened bus and addresses the display at `address`.
+    pub fn new(mut bus: B, address: u16) -> Result<Self, B::Error> {
+        bus.set_slave_address(address)?;
+        Ok(Self { bus, address })
+    }
+
+    /// Returns the slave address the display is written to.
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// Changes the slave address, e.g. after `save_twi_addr` and a reset.
+    pub fn set_address(&mut self, address: u16) -> Result<(), B::Error> {
+        self.bus.set_slave_address(address)?;
+        self.address = address;
+        Ok(())
+    }
+
+    /// Returns a reference to the underlying bus.
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+}
+
+impl<B: I2cBus> Transport for I2cTransport<B> {
+    type Error = B::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bus.write(bytes)
+    }
+}
+
+/// An in-memory I2C bus that records each write with its slave address.
+#[derive(Debug, Default, Clone)]
+pub struct MockI2cBus {
+    address: u16,
+    writes: Vec<(u16, Vec<u8>)>,
+}
+
+impl MockI2cBus {
+    /// Creates an empty mock bus.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns each write made so far together with the address it targeted.
+    pub fn writes(&self) -> &[(u16, Vec<u8>)] {
+        &self.writes
+    }
+}
+
+impl I2cBus for MockI2cBus {
+    type Error = Infallible;
+
+    fn set_slave_address(&mut self, address: u16) -> Result<(), Self::Error> {
+        self.address = address;
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.writes.push((self.address, bytes.to_vec()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::lcds::Lcds;
+
+    fn lcds() -> Lcds<I2cTransport<MockI2cBus>> {
+        Lcds::new(I2cTransport::new(MockI2cBus::new(), TWI_DEFAULT_ADDR).unwrap())
+    }
+
+    fn writes(lcds: &Lcds<I2cTransport<MockI2cBus>>) -> &[(u16, Vec<u8>)] {
+        lcds.transport().bus().writes()
+    }
+
+    #[test]
+    fn each_command_is_one_write_to_the_display() {
+        let mut lcds = lcds();
+        lcds.display_clear().unwrap();
+        lcds.cursor_mode_set(true, true).unwrap();
+        lcds.display_set(true, false).unwrap();
+        assert_eq!(
+            writes(&lcds),
+            [
+                (0x48, b"\x1b[j".to_vec()),
+                (0x48, b"\x1b[2c".to_vec()),
+                (0x48, b"\x1b[1e".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_is_framed_with_its_cursor_move() {
+        let mut lcds = lcds();
+        lcds.write_string_at_pos(1, 12, "OK").unwrap();
+        assert_eq!(writes(&lcds), [(0x48, b"\x1b[1;12HOK".to_vec())]);
+    }
+
+    #[test]
+    fn set_address_redirects_later_writes() {
+        let mut lcds = lcds();
+        lcds.save_twi_addr(0x3C).unwrap();
+        lcds.transport_mut().set_address(0x3C).unwrap();
+        lcds.reset().unwrap();
+        assert_eq!(
+            writes(&lcds),
+            [(0x48, b"\x1b[60a".to_vec()), (0x3C, b"\x1b[0*".to_vec())]
+        );
+        assert_eq!(lcds.transport().address(), 0x3C);
+    }
+}
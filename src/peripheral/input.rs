@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use log::debug;
+use rppal::gpio::{Gpio, InputPin};
+
+/// Default time a raw level must hold before a button change is accepted.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(30);
+
+/// Default hold time that turns a press into a long press.
+pub const DEFAULT_LONG_PRESS: Duration = Duration::from_millis(800);
+
+/// A user input the menu and pages react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    Up,
+    Down,
+    Select,
+    LongSelect,
+    Back,
+}
+
+/// Something that produces input events when polled.
+pub trait InputSource {
+    /// Samples the inputs and returns the next event, if any.
+    fn poll(&mut self, now: Instant) -> Option<InputEvent>;
+}
+
+/// What a debounced button reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Press {
+    /// Pressed and released before the long-press time.
+    Short,
+    /// Held for the long-press time; reported once while still held.
+    Long,
+}
+
+/// Debounces a raw button level and detects long presses.
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    debounce: Duration,
+    long_press: Duration,
+    raw: bool,
+    raw_since: Option<Instant>,
+    pressed_since: Option<Instant>,
+    long_reported: bool,
+}
+
+impl Default for Debouncer {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEBOUNCE, DEFAULT_LONG_PRESS)
+    }
+}
+
+impl Debouncer {
+    /// Creates a debouncer with the given settle and long-press times.
+    pub fn new(debounce: Duration, long_press: Duration) -> Self {
+        Self {
+            debounce,
+            long_press,
+            raw: false,
+            raw_since: None,
+            pressed_since: None,
+            long_reported: false,
+        }
+    }
+
+    /// Returns true while the debounced button is held.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed_since.is_some()
+    }
+
+    /// Feeds a raw sample (true = pressed) and returns a completed press.
+    pub fn update(&mut self, pressed: bool, now: Instant) -> Option<Press> {
+        if pressed != self.raw || self.raw_since.is_none() {
+            self.raw = pressed;
+            self.raw_since = Some(now);
+        }
+        let settled = self.raw_since.is_some_and(|t| now.duration_since(t) >= self.debounce);
+
+        match (self.pressed_since, settled && self.raw) {
+            (None, true) => {
+                self.pressed_since = Some(now);
+                self.long_reported = false;
+                None
+            }
+            (Some(since), true) => {
+                if !self.long_reported && now.duration_since(since) >= self.long_press {
+                    self.long_reported = true;
+                    return Some(Press::Long);
+                }
+                None
+            }
+            (Some(_), false) if settled => {
+                self.pressed_since = None;
+                (!self.long_reported).then_some(Press::Short)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A push button wired between a GPIO pin and ground, using the internal pull-up.
+#[derive(Debug)]
+pub struct GpioButton {
+    pin: InputPin,
+    debouncer: Debouncer,
+    short: InputEvent,
+    long: Option<InputEvent>,
+}
+
+impl GpioButton {
+    /// Opens BCM pin `pin` as a button reporting `short` on a press and
+    /// `long` (if set) on a long press.
+    pub fn new(gpio: &Gpio, pin: u8, short: InputEvent, long: Option<InputEvent>) -> rppal::gpio::Result<Self> {
+        Ok(Self {
+            pin: gpio.get(pin)?.into_input_pullup(),
+            debouncer: Debouncer::default(),
+            short,
+            long,
+        })
+    }
+
+    /// Replaces the debounce and long-press timing.
+    pub fn with_debouncer(mut self, debouncer: Debouncer) -> Self {
+        self.debouncer = debouncer;
+        self
+    }
+}
+
+impl InputSource for GpioButton {
+    fn poll(&mut self, now: Instant) -> Option<InputEvent> {
+        match self.debouncer.update(self.pin.is_low(), now)? {
+            Press::Short => Some(self.short),
+            Press::Long => self.long,
+        }
+    }
+}
+
+/// A quadrature rotary encoder with an optional push switch.
+///
+/// Turning clockwise reports `Down` (next item) and counter-clockwise `Up`,
+/// once per detent. Build the menu with `MenuSystem::with_encoder` so
+/// clockwise also raises values being edited.
+#[derive(Debug)]
+pub struct RotaryEncoder {
+    a: InputPin,
+    b: InputPin,
+    switch: Option<GpioButton>,
+    state: u8,
+    steps: i8,
+    steps_per_detent: i8,
+}
+
+impl RotaryEncoder {
+    /// Opens the encoder's A and B phases on BCM pins `a` and `b`.
+    pub fn new(gpio: &Gpio, a: u8, b: u8) -> rppal::gpio::Result<Self> {
+        let a = gpio.get(a)?.into_input_pullup();
+        let b = gpio.get(b)?.into_input_pullup();
+        let state = (a.is_high() as u8) << 1 | b.is_high() as u8;
+        Ok(Self {
+            a,
+            b,
+            switch: None,
+            state,
+            steps: 0,
+            steps_per_detent: 4,
+        })
+    }
+
+    /// Adds the encoder's push switch: press selects, long press goes back.
+    pub fn with_switch(mut self, gpio: &Gpio, pin: u8) -> rppal::gpio::Result<Self> {
+        self.switch = Some(GpioButton::new(gpio, pin, InputEvent::Select, Some(InputEvent::Back))?);
+        Ok(self)
+    }
+
+    /// Sets how many quadrature steps make one detent (usually 4, some encoders use 2).
+    pub fn with_steps_per_detent(mut self, steps: i8) -> Self {
+        self.steps_per_detent = steps.max(1);
+        self
+    }
+
+    /// Returns +1/-1 for a valid quadrature transition, 0 otherwise.
+    fn transition(prev: u8, next: u8) -> i8 {
+        match (prev << 2) | next {
+            0b0001 | 0b0111 | 0b1110 | 0b1000 => 1,
+            0b0010 | 0b1011 | 0b1101 | 0b0100 => -1,
+            _ => 0,
+        }
+    }
+}
+
+impl InputSource for RotaryEncoder {
+    fn poll(&mut self, now: Instant) -> Option<InputEvent> {
+        if let Some(event) = self.switch.as_mut().and_then(|s| s.poll(now)) {
+            return Some(event);
+        }
+        let next = (self.a.is_high() as u8) << 1 | self.b.is_high() as u8;
+        self.steps += Self::transition(self.state, next);
+        self.state = next;
+        if self.steps >= self.steps_per_detent {
+            self.steps = 0;
+            return Some(InputEvent::Down);
+        }
+        if self.steps <= -self.steps_per_detent {
+            self.steps = 0;
+            return Some(InputEvent::Up);
+        }
+        None
+    }
+}
+
+/// Polls several input sources; events that arrive together are returned
+/// by successive polls.
+#[derive(Default)]
+pub struct InputGroup {
+    sources: Vec<Box<dyn InputSource>>,
+    pending: VecDeque<InputEvent>,
+}
+
+impl InputGroup {
+    /// Creates an empty group.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source to the group.
+    pub fn add<S: InputSource + 'static>(&mut self, source: S) {
+        self.sources.push(Box::new(source));
+    }
+}
+
+impl InputSource for InputGroup {
+    fn poll(&mut self, now: Instant) -> Option<InputEvent> {
+        // Poll every source so each keeps sampling, even if an earlier one fired.
+        for source in self.sources.iter_mut() {
+            if let Some(event) = source.poll(now) {
+                debug!("input event {:?}", event);
+                self.pending.push_back(event);
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// An input source fed from a queue, for driving menus without hardware.
+#[derive(Debug, Default, Clone)]
+pub struct MockInput {
+    events: VecDeque<InputEvent>,
+}
+
+impl MockInput {
+    /// Creates an empty mock input.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues an event to be returned by a later `poll`.
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push_back(event);
+    }
+}
+
+impl InputSource for MockInput {
+    fn poll(&mut self, _now: Instant) -> Option<InputEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn debouncer_ignores_bounces_and_reports_short_press() {
+        let t0 = Instant::now();
+        let mut button = Debouncer::default();
+        assert_eq!(button.update(true, t0), None);
+        assert_eq!(button.update(false, t0 + 10 * MS), None);
+        assert_eq!(button.update(true, t0 + 15 * MS), None);
+        assert_eq!(button.update(true, t0 + 40 * MS), None);
+        assert!(!button.is_pressed(), "level has not held for the debounce time");
+        assert_eq!(button.update(true, t0 + 50 * MS), None);
+        assert!(button.is_pressed());
+        assert_eq!(button.update(false, t0 + 100 * MS), None);
+        assert_eq!(button.update(true, t0 + 110 * MS), None);
+        assert_eq!(button.update(false, t0 + 120 * MS), None);
+        assert_eq!(button.update(false, t0 + 160 * MS), Some(Press::Short));
+        assert!(!button.is_pressed());
+    }
+
+    #[test]
+    fn debouncer_reports_long_press_once_while_held() {
+        let t0 = Instant::now();
+        let mut button = Debouncer::default();
+        button.update(true, t0);
+        button.update(true, t0 + 30 * MS);
+        assert_eq!(button.update(true, t0 + 500 * MS), None);
+        assert_eq!(button.update(true, t0 + 830 * MS), Some(Press::Long));
+        assert_eq!(button.update(true, t0 + 2000 * MS), None);
+        button.update(false, t0 + 2010 * MS);
+        assert_eq!(button.update(false, t0 + 2040 * MS), None, "a long press is not also a short one");
+        assert!(!button.is_pressed());
+    }
+
+    #[test]
+    fn mock_input_returns_events_in_order() {
+        let now = Instant::now();
+        let mut input = MockInput::new();
+        input.push(InputEvent::Down);
+        input.push(InputEvent::Select);
+        assert_eq!(input.poll(now), Some(InputEvent::Down));
+        assert_eq!(input.poll(now), Some(InputEvent::Select));
+        assert_eq!(input.poll(now), None);
+    }
+}
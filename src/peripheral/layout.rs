@@ -0,0 +1,152 @@
+/// How a value sits inside a field wider than the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+    Center,
+}
+
+/// A fixed-width area of a row that values are written into.
+///
+/// Writing a value always fills the whole field: shorter values are padded
+/// and longer ones truncated, so a shrinking number never leaves stale
+/// digits behind. Register fields by name with `Lcds::define_field` and
+/// update them with `Lcds::write_field` or `lcds_write_field!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub row: u8,
+    pub col: u8,
+    pub width: u8,
+    pub align: Align,
+    /// Character code used for padding.
+    pub pad: u8,
+}
+
+impl Field {
+    /// Creates a left-aligned, space-padded field.
+    pub fn new(row: u8, col: u8, width: u8) -> Self {
+        Self { row, col, width, align: Align::Left, pad: b' ' }
+    }
+
+    /// Sets the alignment.
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Sets the padding character code, e.g. `b'0'` for zero-filled numbers.
+    pub fn with_pad(mut self, pad: u8) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    /// Fits encoded text into the field, returning exactly `width` codes.
+    ///
+    /// Right-aligned values that do not fit keep their last characters, so
+    /// the least significant digits of a number stay visible.
+    pub fn fit(&self, codes: &[u8]) -> Vec<u8> {
+        let width = self.width as usize;
+        if codes.len() >= width {
+            return match self.align {
+                Align::Right => codes[codes.len() - width..].to_vec(),
+                Align::Left | Align::Center => codes[..width].to_vec(),
+            };
+        }
+        let spare = width - codes.len();
+        let before = match self.align {
+            Align::Left => 0,
+            Align::Right => spare,
+            Align::Center => spare / 2,
+        };
+        let mut out = vec![self.pad; width];
+        out[before..before + codes.len()].copy_from_slice(codes);
+        out
+    }
+}
+
+/// Writes formatted text at a row and column, like `write!`.
+///
+/// ```text
+/// lcds_write!(lcds, 0, 0, "Moist: {:>3}%", moisture)?;
+/// ```
+#[macro_export]
+macro_rules! lcds_write {
+    ($lcds:expr, $row:expr, $col:expr, $($arg:tt)*) => {
+        $lcds.write_fmt_at($row, $col, format_args!($($arg)*))
+    };
+}
+
+/// Writes formatted text into a named field, like `write!`.
+///
+/// ```text
+/// lcds_write_field!(lcds, "moisture", "{}%", moisture)?;
+/// ```
+#[macro_export]
+macro_rules! lcds_write_field {
+    ($lcds:expr, $name:expr, $($arg:tt)*) => {
+        $lcds.write_field($name, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::emulator::LcdsEmulator;
+    use crate::peripheral::lcds::{Lcds, LcdsError};
+
+    fn fit(field: Field, text: &str) -> String {
+        String::from_utf8(field.fit(text.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn short_values_are_padded_by_alignment() {
+        let field = Field::new(0, 0, 6);
+        assert_eq!(fit(field, "42"), "42    ");
+        assert_eq!(fit(field.with_align(Align::Right), "42"), "    42");
+        assert_eq!(fit(field.with_align(Align::Center), "42"), "  42  ");
+        // Odd spare cells go after a centred value.
+        assert_eq!(fit(field.with_align(Align::Center), "7"), "  7   ");
+        assert_eq!(fit(field.with_align(Align::Right).with_pad(b'0'), "42"), "000042");
+    }
+
+    #[test]
+    fn long_values_are_truncated_by_alignment() {
+        let field = Field::new(0, 0, 4);
+        assert_eq!(fit(field, "123456"), "1234");
+        assert_eq!(fit(field.with_align(Align::Center), "123456"), "1234");
+        assert_eq!(fit(field.with_align(Align::Right), "123456"), "3456");
+        assert_eq!(fit(field, "abcd"), "abcd");
+        assert_eq!(fit(Field::new(0, 0, 0), "abc"), "");
+    }
+
+    #[test]
+    fn lcds_write_formats_at_a_position() {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        crate::lcds_write!(lcds, 1, 2, "Moist: {:>3}%", 7).unwrap();
+        assert_eq!(lcds.transport().line(1), "  Moist:   7%   ");
+    }
+
+    #[test]
+    fn lcds_write_field_overwrites_the_whole_field() {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        lcds.write_string_at_pos(0, 0, "Moist:     %").unwrap();
+        lcds.define_field("moisture", Field::new(0, 7, 4).with_align(Align::Right)).unwrap();
+        crate::lcds_write_field!(lcds, "moisture", "{}", 100).unwrap();
+        assert_eq!(lcds.transport().line(0), "Moist:  100%    ");
+        crate::lcds_write_field!(lcds, "moisture", "{}", 9).unwrap();
+        assert_eq!(lcds.transport().line(0), "Moist:    9%    ");
+    }
+
+    #[test]
+    fn fields_must_exist_and_fit_on_the_row() {
+        let mut lcds = Lcds::new(LcdsEmulator::new_2x16());
+        assert!(matches!(
+            crate::lcds_write_field!(lcds, "missing", "{}", 1),
+            Err(LcdsError::UnknownField(name)) if name == "missing"
+        ));
+        assert!(matches!(lcds.define_field("wide", Field::new(0, 36, 6)), Err(LcdsError::ColRange(41))));
+        assert!(matches!(lcds.define_field("low", Field::new(3, 0, 6)), Err(LcdsError::RowRange(3))));
+        assert!(lcds.field("wide").is_none());
+    }
+}
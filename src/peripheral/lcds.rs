@@ -0,0 +1,1101 @@
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, trace, warn};
+use prometheus::{IntCounter, Opts, Registry};
+use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+
+use super::charset::Charset;
+use super::layout::Field;
+use super::transport::Transport;
+
+/*
+    DIGILENT CODES
+*/
+// Command constants
+pub(crate) const ESC: u8 = 0x1B;
+pub(crate) const BRACKET: u8 = 0x5B; // [
+pub(crate) const CURSOR_POS_CMD: u8 = 0x48; // H
+pub(crate) const CURSOR_SAVE_CMD: u8 = 0x73; // s
+pub(crate) const CURSOR_RSTR_CMD: u8 = 0x75; // u
+pub(crate) const DISP_CLR_CMD: u8 = 0x6A; // j
+pub(crate) const ERASE_INLINE_CMD: u8 = 0x4B; // K
+pub(crate) const ERASE_FIELD_CMD: u8 = 0x4E; // N
+pub(crate) const LSCROLL_CMD: u8 = 0x40; // @
+pub(crate) const RSCROLL_CMD: u8 = 0x41; // A
+pub(crate) const RST_CMD: u8 = 0x2A; // *
+pub(crate) const DISP_EN_CMD: u8 = 0x65; // e
+pub(crate) const DISP_MODE_CMD: u8 = 0x68; // h
+pub(crate) const CURSOR_MODE_CMD: u8 = 0x63; // c
+pub(crate) const TWI_SAVE_ADDR_CMD: u8 = 0x61; // a
+pub(crate) const BR_SAVE_CMD: u8 = 0x62; // b
+pub(crate) const PRG_CHAR_CMD: u8 = 0x70; // p
+pub(crate) const SAVE_RAM_TO_EEPROM_CMD: u8 = 0x74; // t
+pub(crate) const LD_EEPROM_TO_RAM_CMD: u8 = 0x6C; // l
+pub(crate) const DEF_CHAR_CMD: u8 = 0x64; // d
+pub(crate) const COMM_MODE_SAVE_CMD: u8 = 0x6D; // m
+pub(crate) const EEPROM_WR_EN_CMD: u8 = 0x77; // w
+pub(crate) const CURSOR_MODE_SAVE_CMD: u8 = 0x6E; // n
+pub(crate) const DISP_MODE_SAVE_CMD: u8 = 0x6F; // o
+
+// Access parameters for communication ports
+pub const PAR_ACCESS_DSPI0: u8 = 0;
+pub const PAR_ACCESS_DSPI1: u8 = 1;
+pub const PAR_SPD_MAX: u32 = 625_000;
+
+/// `device` label of the metrics of a driver created with `new`.
+pub const DEFAULT_DEVICE: &str = "lcds";
+
+// Error definitions
+/// Errors reported by the LCDS driver.
+///
+/// The argument variants mirror the `LCDS_ERR_ARG_*` codes of the Digilent
+/// reference library and carry the rejected value.
+#[derive(Debug)]
+pub enum LcdsError {
+    /// The row is not within the 0-2 range.
+    RowRange(u8),
+    /// The column is not within the 0-39 range.
+    ColRange(u8),
+    /// The erase option is not within the 0-2 range.
+    EraseOptions(u8),
+    /// The baud rate index is not within the 0-6 range, or the host port
+    /// does not support its rate.
+    BaudRateRange(u8),
+    /// The character table is not within the 0-3 range.
+    TableRange(u8),
+    /// The communication mode is not within the 0-7 range.
+    CommRange(u8),
+    /// The cursor mode is not within the 0-2 range.
+    CursorRange(u8),
+    /// The display mode is not within the 0-1 range.
+    DisplayRange(u8),
+    /// The user character position is not within the 0-7 range.
+    PositionRange(u8),
+    /// The TWI address does not fit in 7 bits.
+    TwiAddress(u8),
+    /// The SPI clock speed is zero or above `PAR_SPD_MAX`.
+    ClockSpeed(u32),
+    /// The SPI mode is not supported by the PmodCLS.
+    SpiMode(Mode),
+    /// No field has been defined under this name.
+    UnknownField(String),
+    /// The underlying transport could not be opened or failed to write.
+    Transport(Box<dyn Error + Send + Sync>),
+}
+
+impl LcdsError {
+    /// Returns the matching `LCDS_ERR_*` code from the reference library, or
+    /// `None` for errors the reference library has no code for.
+    pub fn code(&self) -> Option<u8> {
+        match self {
+            LcdsError::RowRange(_) => Some(1),
+            LcdsError::ColRange(_) => Some(2),
+            LcdsError::EraseOptions(_) => Some(3),
+            LcdsError::BaudRateRange(_) => Some(4),
+            LcdsError::TableRange(_) => Some(5),
+            LcdsError::CommRange(_) => Some(6),
+            LcdsError::CursorRange(_) => Some(7),
+            LcdsError::DisplayRange(_) => Some(8),
+            LcdsError::PositionRange(_) => Some(9),
+            LcdsError::TwiAddress(_)
+            | LcdsError::ClockSpeed(_)
+            | LcdsError::SpiMode(_)
+            | LcdsError::UnknownField(_)
+            | LcdsError::Transport(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for LcdsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LcdsError::RowRange(v) => write!(f, "row {} is not within 0-2", v),
+            LcdsError::ColRange(v) => write!(f, "column {} is not within 0-39", v),
+            LcdsError::EraseOptions(v) => write!(f, "erase option {} is not within 0-2", v),
+            LcdsError::BaudRateRange(v) => write!(f, "baud rate index {} is not within 0-6 or not supported", v),
+            LcdsError::TableRange(v) => write!(f, "character table {} is not within 0-3", v),
+            LcdsError::CommRange(v) => write!(f, "communication mode {} is not within 0-7", v),
+            LcdsError::CursorRange(v) => write!(f, "cursor mode {} is not within 0-2", v),
+            LcdsError::DisplayRange(v) => write!(f, "display mode {} is not within 0-1", v),
+            LcdsError::PositionRange(v) => write!(f, "character position {} is not within 0-7", v),
+            LcdsError::TwiAddress(v) => write!(f, "TWI address {:#04X} is not within 0x00-0x7F", v),
+            LcdsError::ClockSpeed(v) => write!(f, "clock speed {} Hz is not within 1-{} Hz", v, PAR_SPD_MAX),
+            LcdsError::SpiMode(m) => write!(f, "SPI mode {:?} is not supported, use Mode0", m),
+            LcdsError::UnknownField(name) => write!(f, "no field named {:?}", name),
+            LcdsError::Transport(e) => write!(f, "LCDS transport error: {}", e),
+        }
+    }
+}
+
+impl Error for LcdsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LcdsError::Transport(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Result type returned by the LCDS driver.
+pub type Result<T> = std::result::Result<T, LcdsError>;
+
+/// Cursor display mode, as set by `cursor_mode_set` (0: off, 1: on, 2: blink).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorMode {
+    Off,
+    On,
+    Blink,
+}
+
+// Other defines
+const MAX: usize = 150;
+
+/// Time the controller needs after a slow command before it takes the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandDelays {
+    /// After `reset`, while the controller restarts.
+    pub reset: Duration,
+    /// After `display_clear`.
+    pub clear: Duration,
+    /// After each EEPROM save.
+    pub eeprom: Duration,
+}
+
+impl Default for CommandDelays {
+    fn default() -> Self {
+        Self {
+            reset: Duration::from_millis(100),
+            clear: Duration::from_millis(2),
+            eeprom: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Driver for the Digilent PmodCLS character display.
+///
+/// The driver is generic over the [`Transport`] the escape sequences are
+/// written to, so the same command logic works over SPI, TWI, UART or an
+/// in-memory buffer. A driver always owns a ready transport; use
+/// [`LcdsBuilder`] to open one on an SPI bus.
+///
+/// Text is encoded for the character ROM through the driver's [`Charset`].
+///
+/// Inside [`Lcds::batch`] commands are collected and sent as one transfer.
+/// Commands the controller needs time to process (reset, clear, EEPROM
+/// saves) end the current transfer, and the next one waits out the
+/// matching [`CommandDelays`] entry.
+pub struct Lcds<T: Transport> {
+    transport: T,
+    charset: Charset,
+    fields: Vec<(String, Field)>,
+    delays: CommandDelays,
+    batch: Option<Vec<u8>>,
+    ready_at: Option<Instant>,
+    display_state: Option<(bool, bool)>,
+    metrics: Metrics,
+}
+
+/// Transfer counters of one display, labelled with its `device` name.
+struct Metrics {
+    transactions: IntCounter,
+    bytes_sent: IntCounter,
+}
+
+impl Metrics {
+    fn new(device: &str) -> Self {
+        let counter = |name: &str, help: &str| {
+            IntCounter::with_opts(Opts::new(name, help).const_label("device", device)).unwrap()
+        };
+        Self {
+            transactions: counter("lcds_transactions_total", "Transfers written to the LCDS transport"),
+            bytes_sent: counter("lcds_bytes_sent_total", "Bytes written to the LCDS transport"),
+        }
+    }
+}
+
+/// Configures and opens an SPI-connected [`Lcds`].
+///
+/// Defaults match the Digilent reference library: `Spi0`, `Ss0`, SPI mode 0
+/// and the `PAR_SPD_MAX` clock of 625 kHz.
+#[derive(Debug, Clone)]
+pub struct LcdsBuilder {
+    bus: Bus,
+    slave_select: SlaveSelect,
+    clock_speed: u32,
+    mode: Mode,
+}
+
+impl Default for LcdsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LcdsBuilder {
+    /// Creates a builder with the reference library defaults.
+    pub fn new() -> Self {
+        Self {
+            bus: Bus::Spi0,
+            slave_select: SlaveSelect::Ss0,
+            clock_speed: PAR_SPD_MAX,
+            mode: Mode::Mode0,
+        }
+    }
+
+    /// Sets the SPI bus to use (e.g., Bus::Spi0).
+    pub fn bus(mut self, bus: Bus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    /// Sets the slave select line.
+    pub fn slave_select(mut self, slave_select: SlaveSelect) -> Self {
+        self.slave_select = slave_select;
+        self
+    }
+
+    /// Sets the SPI clock speed in Hz (at most `PAR_SPD_MAX`).
+    pub fn clock_speed(mut self, clock_speed: u32) -> Self {
+        self.clock_speed = clock_speed;
+        self
+    }
+
+    /// Sets the SPI mode. The PmodCLS only supports Mode::Mode0.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Validates the configuration and opens the SPI interface.
+    ///
+    /// # Errors
+    /// * Returns `ClockSpeed` or `SpiMode` for settings the PmodCLS cannot
+    ///   use, or a transport error if the SPI device cannot be opened.
+    pub fn build(self) -> Result<Lcds<Spi>> {
+        if self.clock_speed == 0 || self.clock_speed > PAR_SPD_MAX {
+            return Err(LcdsError::ClockSpeed(self.clock_speed));
+        }
+        if self.mode != Mode::Mode0 {
+            return Err(LcdsError::SpiMode(self.mode));
+        }
+        let spi = Spi::new(self.bus, self.slave_select, self.clock_speed, self.mode)
+            .map_err(|e| {
+                error!("Failed to open {} {}: {:?}", self.bus, self.slave_select, e);
+                LcdsError::Transport(Box::new(e))
+            })?;
+        let device = format!("spidev{}.{}", self.bus as u8, self.slave_select as u8);
+        Ok(Lcds::new(spi).with_device(&device))
+    }
+}
+
+impl<T: Transport> Lcds<T> {
+    /// Creates a new LCDS instance that writes to the given transport.
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            charset: Charset::new(),
+            fields: Vec::new(),
+            delays: CommandDelays::default(),
+            batch: None,
+            ready_at: None,
+            display_state: None,
+            metrics: Metrics::new(DEFAULT_DEVICE),
+        }
+    }
+
+    /// Replaces the delays observed after slow commands.
+    pub fn with_delays(mut self, delays: CommandDelays) -> Self {
+        self.delays = delays;
+        self
+    }
+
+    /// Names the display in the `device` label of its metrics, so several
+    /// displays can share a registry. `LcdsBuilder::build` uses the spidev
+    /// name, e.g. `spidev0.1`.
+    ///
+    /// Replaces the counters with fresh ones, so call it before registering.
+    pub fn with_device(mut self, device: &str) -> Self {
+        self.metrics = Metrics::new(device);
+        self
+    }
+
+    /// Registers the transaction and byte counters with a Prometheus registry.
+    pub fn register_metrics(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.metrics.transactions.clone()))?;
+        registry.register(Box::new(self.metrics.bytes_sent.clone()))
+    }
+
+    /// Returns the number of transfers written so far.
+    pub fn transactions(&self) -> u64 {
+        self.metrics.transactions.get()
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn bytes_sent(&self) -> u64 {
+        self.metrics.bytes_sent.get()
+    }
+
+    /// Replaces the charset used to encode text.
+    pub fn with_charset(mut self, charset: Charset) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Returns the charset used to encode text.
+    pub fn charset(&self) -> &Charset {
+        &self.charset
+    }
+
+    /// Returns a mutable reference to the charset, e.g. to add substitutions.
+    pub fn charset_mut(&mut self) -> &mut Charset {
+        &mut self.charset
+    }
+
+    /// Returns a reference to the underlying transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Returns a mutable reference to the underlying transport.
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Consumes the driver and returns the underlying transport.
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// Runs `f` with every command it issues collected into one transfer.
+    ///
+    /// Nested batches join the outer one. Whatever was collected is sent even
+    /// if `f` fails, as it would have been without batching.
+    ///
+    /// # Errors
+    /// * Returns the error of `f`, or a transport error if the write fails.
+    pub fn batch<R, F>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Self) -> Result<R>,
+    {
+        if self.batch.is_some() {
+            return f(self);
+        }
+        self.batch = Some(Vec::new());
+        let result = f(self);
+        let pending = self.batch.take().unwrap_or_default();
+        let sent = self.transfer(&pending);
+        let value = result?;
+        sent?;
+        Ok(value)
+    }
+
+    /// Blocks until the controller has had time to process the last slow command.
+    pub fn wait_ready(&mut self) {
+        if let Some(ready_at) = self.ready_at.take() {
+            let now = Instant::now();
+            if ready_at > now {
+                thread::sleep(ready_at - now);
+            }
+        }
+    }
+
+    fn send_bytes(&mut self, bytes: &[u8], context: &str) -> Result<()> {
+        trace!("{} command: {:?}", context, bytes);
+        match self.batch.as_mut() {
+            Some(batch) => {
+                batch.extend_from_slice(bytes);
+                Ok(())
+            }
+            None => self.transfer(bytes),
+        }
+    }
+
+    /// Sends a command the controller needs `delay` to process, flushing the
+    /// current batch so nothing queued after it is sent too early.
+    fn send_slow(&mut self, bytes: &[u8], context: &str, delay: Duration) -> Result<()> {
+        self.send_bytes(bytes, context)?;
+        if let Some(batch) = self.batch.as_mut() {
+            let pending = std::mem::take(batch);
+            self.transfer(&pending)?;
+        }
+        self.ready_at = Some(Instant::now() + delay);
+        Ok(())
+    }
+
+    fn transfer(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.wait_ready();
+        match self.transport.write(bytes) {
+            Ok(()) => {
+                self.metrics.transactions.inc();
+                self.metrics.bytes_sent.inc_by(bytes.len() as u64);
+                trace!("transfer of {} bytes sent", bytes.len());
+                Ok(())
+            }
+            Err(e) => {
+                error!("Transport write failed after {} bytes queued: {:?}", bytes.len(), e);
+                Err(LcdsError::Transport(Box::new(e)))
+            }
+        }
+    }
+
+    /// Encodes text with the charset, logging characters it cannot show.
+    fn encode(&self, text: &str, context: &str) -> Vec<u8> {
+        self.charset.encode(text).unwrap_or_else(|e| {
+            warn!("{}: {}", context, e);
+            self.charset.encode_lossy(text)
+        })
+    }
+
+    /// Builds `ESC [ <value> <cmd>` with the value in decimal.
+    fn decimal_cmd(value: u8, cmd: u8) -> Vec<u8> {
+        let mut bytes = vec![ESC, BRACKET];
+        bytes.extend_from_slice(value.to_string().as_bytes());
+        bytes.push(cmd);
+        bytes
+    }
+
+    fn check_pos(idx_row: u8, idx_col: u8) -> Result<()> {
+        if idx_row > 2 {
+            return Err(LcdsError::RowRange(idx_row));
+        }
+        if idx_col > 39 {
+            return Err(LcdsError::ColRange(idx_col));
+        }
+        Ok(())
+    }
+
+    /// Sets the display and backlight state.
+    ///
+    /// The state is remembered, see `display_state`.
+    ///
+    /// # Arguments
+    /// * `set_display` - If true, turns the display on; otherwise, off.
+    /// * `set_bckl` - If true, turns the backlight on; otherwise, off.
+    pub fn display_set(&mut self, set_display: bool, set_bckl: bool) -> Result<()> {
+        let msg = match (set_display, set_bckl) {
+            (false, false) => [ESC, BRACKET, b'0', DISP_EN_CMD],
+            (true, false) => [ESC, BRACKET, b'1', DISP_EN_CMD],
+            (false, true) => [ESC, BRACKET, b'2', DISP_EN_CMD],
+            (true, true) => [ESC, BRACKET, b'3', DISP_EN_CMD],
+        };
+        self.send_bytes(&msg, "display_set")?;
+        self.display_state = Some((set_display, set_bckl));
+        Ok(())
+    }
+
+    /// Returns the display and backlight state last sent with `display_set`,
+    /// or `None` if it was never set. The controller cannot be read back, so
+    /// this is what a reset or brown-out needs to restore.
+    pub fn display_state(&self) -> Option<(bool, bool)> {
+        self.display_state
+    }
+
+    /// Sets the cursor and blink mode.
+    ///
+    /// # Arguments
+    /// * `set_cursor` - If true, shows the cursor; otherwise, hides it.
+    /// * `set_blink` - If true, enables cursor blinking; otherwise, disables it.
+    pub fn cursor_mode_set(&mut self, set_cursor: bool, set_blink: bool) -> Result<()> {
+        let msg = match (set_cursor, set_blink) {
+            (false, _) => [ESC, BRACKET, b'0', CURSOR_MODE_CMD],
+            (true, false) => [ESC, BRACKET, b'1', CURSOR_MODE_CMD],
+            (true, true) => [ESC, BRACKET, b'2', CURSOR_MODE_CMD],
+        };
+        self.send_bytes(&msg, "cursor_mode_set")
+    }
+
+    /// Clears the display and returns the cursor home.
+    pub fn display_clear(&mut self) -> Result<()> {
+        let disp_clr = &[ESC, BRACKET, DISP_CLR_CMD];
+        let delay = self.delays.clear;
+        self.send_slow(disp_clr, "display_clear", delay)
+    }
+
+    /// Writes a string at a specified position on the display.
+    ///
+    /// The string is encoded with the driver's charset and truncated to the
+    /// cells left on the row. Characters the display cannot show are logged
+    /// and written as `charset::REPLACEMENT`.
+    ///
+    /// # Arguments
+    /// * `idx_row` - The row index (0-2).
+    /// * `idx_col` - The column index (0-39).
+    /// * `str_ln` - The string to write.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn write_string_at_pos(&mut self, idx_row: u8, idx_col: u8, str_ln: &str) -> Result<()> {
+        Self::check_pos(idx_row, idx_col)?;
+        let mut codes = self.encode(str_ln, "write_string_at_pos");
+        codes.truncate(40 - idx_col as usize);
+        self.batch(|lcds| {
+            lcds.set_pos(idx_row, idx_col)?;
+            lcds.send_bytes(&codes, "write_string_at_pos: data")
+        })
+    }
+
+    /// Writes formatted text at a specified position, see `lcds_write!`.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn write_fmt_at(&mut self, idx_row: u8, idx_col: u8, args: fmt::Arguments<'_>) -> Result<()> {
+        self.write_string_at_pos(idx_row, idx_col, &fmt::format(args))
+    }
+
+    /// Registers a field under `name`, replacing any field of the same name.
+    ///
+    /// # Errors
+    /// * Returns `RowRange` or `ColRange` if the field does not fit on the row.
+    pub fn define_field(&mut self, name: &str, field: Field) -> Result<()> {
+        Self::check_pos(field.row, field.col)?;
+        let end = field.col as usize + field.width as usize;
+        if end > 40 {
+            return Err(LcdsError::ColRange((end - 1).min(u8::MAX as usize) as u8));
+        }
+        self.fields.retain(|(n, _)| n != name);
+        self.fields.push((name.to_string(), field));
+        Ok(())
+    }
+
+    /// Returns the field registered under `name`.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, f)| f)
+    }
+
+    /// Writes a value into the named field, padding or truncating it to the
+    /// field width. See also `lcds_write_field!`.
+    ///
+    /// # Errors
+    /// * Returns `UnknownField` if no such field is defined, or a transport
+    ///   error if the write fails.
+    pub fn write_field<V: fmt::Display>(&mut self, name: &str, value: V) -> Result<()> {
+        let field = *self.field(name).ok_or_else(|| LcdsError::UnknownField(name.to_string()))?;
+        self.write_in(&field, value)
+    }
+
+    /// Writes a value into an unnamed field.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn write_in<V: fmt::Display>(&mut self, field: &Field, value: V) -> Result<()> {
+        let codes = self.encode(&value.to_string(), "write_in");
+        let mut cells = field.fit(&codes);
+        cells.truncate(40usize.saturating_sub(field.col as usize));
+        self.batch(|lcds| {
+            lcds.set_pos(field.row, field.col)?;
+            lcds.send_bytes(&cells, "write_in: data")
+        })
+    }
+
+    /// Scrolls the display left or right by a specified number of columns.
+    ///
+    /// # Arguments
+    /// * `direction` - true for right, false for left.
+    /// * `idx_col` - Number of columns to scroll (0-39).
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn display_scroll(&mut self, direction: bool, idx_col: u8) -> Result<()> {
+        if idx_col > 39 {
+            return Err(LcdsError::ColRange(idx_col));
+        }
+        let first_digit = idx_col % 10;
+        let second_digit = idx_col / 10;
+        let r_scroll = &[ESC, BRACKET, second_digit + b'0', first_digit + b'0', RSCROLL_CMD];
+        let l_scroll = &[ESC, BRACKET, second_digit + b'0', first_digit + b'0', LSCROLL_CMD];
+        self.batch(|lcds| {
+            lcds.display_mode(true)?;
+            if direction {
+                lcds.send_bytes(r_scroll, "right scroll")
+            } else {
+                lcds.send_bytes(l_scroll, "left scroll")
+            }
+        })
+    }
+
+    /// Saves the current cursor position.
+    pub fn save_cursor(&mut self) -> Result<()> {
+        let save_cursor = &[ESC, BRACKET, b'0', CURSOR_SAVE_CMD];
+        self.send_bytes(save_cursor, "save_cursor")
+    }
+
+    /// Restores the previously saved cursor position.
+    pub fn restore_cursor(&mut self) -> Result<()> {
+        let rest_cursor = &[ESC, BRACKET, b'0', CURSOR_RSTR_CMD];
+        self.send_bytes(rest_cursor, "restore_cursor")
+    }
+
+    /// Sets the display mode to wrap at 16 or 40 characters.
+    ///
+    /// # Arguments
+    /// * `char_number` - true for 16 chars, false for 40 chars.
+    pub fn display_mode(&mut self, char_number: bool) -> Result<()> {
+        let disp_mode_16 = &[ESC, BRACKET, b'0', DISP_MODE_CMD];
+        let disp_mode_40 = &[ESC, BRACKET, b'1', DISP_MODE_CMD];
+
+        if char_number {
+            self.send_bytes(disp_mode_16, "display mode 16")
+        } else {
+            self.send_bytes(disp_mode_40, "display mode 40")
+        }
+    }
+
+    /// Erases characters from a line based on the erase parameter.
+    ///
+    /// # Arguments
+    /// * `erase_param` - 0: from current position to end of line, 1: start of line to current position, 2: entire line.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn erase_in_line(&mut self, erase_param: u8) -> Result<()> {
+        if erase_param > 2 {
+            return Err(LcdsError::EraseOptions(erase_param));
+        }
+        let erase_mode = &[ESC, BRACKET, erase_param + b'0', ERASE_INLINE_CMD];
+        self.send_bytes(erase_mode, "erase mode")
+    }
+
+    /// Erases a number of characters starting at the current cursor position.
+    ///
+    /// # Arguments
+    /// * `chars_number` - Number of characters to erase, sent as a decimal
+    ///   parameter.
+    pub fn erase_chars(&mut self, chars_number: u8) -> Result<()> {
+        let erase_chars = Self::decimal_cmd(chars_number, ERASE_FIELD_CMD);
+        self.send_bytes(&erase_chars, "erasing chars at cursor")
+    }
+
+    /// Resets (cycles power of) the LCDS device.
+    pub fn reset(&mut self) -> Result<()> {
+        let reset = &[ESC, BRACKET, b'0', RST_CMD];
+        let delay = self.delays.reset;
+        self.send_slow(reset, "reset LCDS", delay)
+    }
+
+    /// Saves the TWI address to EEPROM.
+    ///
+    /// # Arguments
+    /// * `addr_eeprom` - The 7-bit TWI address to save, sent as a decimal
+    ///   parameter.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn save_twi_addr(&mut self, addr_eeprom: u8) -> Result<()> {
+        if addr_eeprom > 0x7F {
+            return Err(LcdsError::TwiAddress(addr_eeprom));
+        }
+        let save_addr = Self::decimal_cmd(addr_eeprom, TWI_SAVE_ADDR_CMD);
+        let delay = self.delays.eeprom;
+        self.send_slow(&save_addr, "saving twi address", delay)
+    }
+
+    /// Saves the baud rate value to EEPROM.
+    ///
+    /// # Arguments
+    /// * `baud_rate` - The baud rate index (0-6), see `uart::BAUD_RATES`.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn save_br(&mut self, baud_rate: u8) -> Result<()> {
+        if baud_rate > 6 {
+            return Err(LcdsError::BaudRateRange(baud_rate));
+        }
+        let save_br = &[ESC, BRACKET, baud_rate + b'0', BR_SAVE_CMD];
+        let delay = self.delays.eeprom;
+        self.send_slow(save_br, "saving baud rate", delay)
+    }
+
+    /// Programs a character table into the LCD.
+    ///
+    /// # Arguments
+    /// * `char_table` - The character table index.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn chars_to_lcd(&mut self, char_table: u8) -> Result<()> {
+        if char_table > 3 {
+            return Err(LcdsError::TableRange(char_table));
+        }
+        let progr_table = &[ESC, BRACKET, char_table + b'0', PRG_CHAR_CMD];
+        self.send_bytes(progr_table, "programming char table")
+    }
+
+    /// Saves a RAM character table to EEPROM.
+    ///
+    /// # Arguments
+    /// * `char_table` - The character table index.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn save_ram_to_eeprom(&mut self, char_table: u8) -> Result<()> {
+        if char_table > 3 {
+            return Err(LcdsError::TableRange(char_table));
+        }
+        let progr_table = &[ESC, BRACKET, char_table + b'0', SAVE_RAM_TO_EEPROM_CMD];
+        let delay = self.delays.eeprom;
+        self.send_slow(progr_table, "save_ram_to_eeprom", delay)
+    }
+
+    /// Loads a character table from EEPROM into RAM.
+    ///
+    /// # Arguments
+    /// * `char_table` - The character table index.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn ld_eeprom_to_ram(&mut self, char_table: u8) -> Result<()> {
+        if char_table > 3 {
+            return Err(LcdsError::TableRange(char_table));
+        }
+        let ld_table = &[ESC, BRACKET, char_table + b'0', LD_EEPROM_TO_RAM_CMD];
+        self.send_bytes(ld_table, "ld_eeprom_to_ram")
+    }
+
+    /// Saves the communication mode to EEPROM.
+    ///
+    /// # Arguments
+    /// * `comm_sel` - The communication mode selection parameter.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn save_comm_to_eeprom(&mut self, comm_sel: u8) -> Result<()> {
+        if comm_sel > 7 {
+            return Err(LcdsError::CommRange(comm_sel));
+        }
+        let cmd = &[ESC, BRACKET, comm_sel + b'0', COMM_MODE_SAVE_CMD];
+        let delay = self.delays.eeprom;
+        self.send_slow(cmd, "save_comm_to_eeprom", delay)
+    }
+
+    /// Enables the write operation to EEPROM.
+    pub fn eeprom_wr_en(&mut self) -> Result<()> {
+        let cmd = &[ESC, BRACKET, b'0', EEPROM_WR_EN_CMD];
+        self.send_bytes(cmd, "eeprom_wr_en")
+    }
+
+    /// Saves the cursor mode into EEPROM.
+    ///
+    /// # Arguments
+    /// * `mode_crs` - The cursor mode parameter (0: off, 1: on, 2: blink).
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn save_cursor_to_eeprom(&mut self, mode_crs: u8) -> Result<()> {
+        if mode_crs > 2 {
+            return Err(LcdsError::CursorRange(mode_crs));
+        }
+        let cmd = &[ESC, BRACKET, mode_crs + b'0', CURSOR_MODE_SAVE_CMD];
+        let delay = self.delays.eeprom;
+        self.send_slow(cmd, "save_cursor_to_eeprom", delay)
+    }
+
+    /// Saves the display mode into EEPROM.
+    ///
+    /// # Arguments
+    /// * `mode_disp` - The display mode parameter (0: 16 chars, 1: 40 chars).
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn save_display_to_eeprom(&mut self, mode_disp: u8) -> Result<()> {
+        if mode_disp > 1 {
+            return Err(LcdsError::DisplayRange(mode_disp));
+        }
+        let cmd = &[ESC, BRACKET, mode_disp + b'0', DISP_MODE_SAVE_CMD];
+        let delay = self.delays.eeprom;
+        self.send_slow(cmd, "save_display_to_eeprom", delay)
+    }
+
+    /// Defines a character in memory at a specified location.
+    ///
+    /// # Arguments
+    /// * `str_user_def` - The user-defined character data (8 bytes, one per row).
+    /// * `char_pos` - The position in memory (0-7).
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn define_user_char(&mut self, str_user_def: &[u8], char_pos: u8) -> Result<()> {
+        if char_pos > 7 {
+            return Err(LcdsError::PositionRange(char_pos));
+        }
+        let mut cmd: Vec<u8> = Vec::with_capacity(MAX);
+        cmd.push(ESC);
+        cmd.push(BRACKET);
+        self.build_user_def_char(str_user_def, &mut cmd);
+        cmd.push(char_pos + b'0');
+        cmd.push(DEF_CHAR_CMD);
+        cmd.push(ESC);
+        cmd.push(BRACKET);
+        cmd.push(b'3');
+        cmd.push(PRG_CHAR_CMD);
+        self.send_bytes(&cmd, "define_user_char")
+    }
+
+    /// Displays a user-defined character at the specified position.
+    ///
+    /// # Arguments
+    /// * `char_pos` - Array of character positions.
+    /// * `char_number` - Number of characters to display.
+    /// * `idx_row` - Row index.
+    /// * `idx_col` - Column index.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn disp_user_char(&mut self, char_pos: &[u8], char_number: u8, idx_row: u8, idx_col: u8) -> Result<()> {
+        Self::check_pos(idx_row, idx_col)?;
+        let to_send = &char_pos[..(char_number as usize).min(char_pos.len())];
+        self.batch(|lcds| {
+            lcds.set_pos(idx_row, idx_col)?;
+            lcds.send_bytes(to_send, "disp_user_char")
+        })
+    }
+
+    /// Writes raw character codes at the current cursor position.
+    ///
+    /// Unlike `write_string_at_pos` this does not move the cursor first, and
+    /// accepts any character ROM code, including user characters 0-7.
+    ///
+    /// # Arguments
+    /// * `data` - The character codes to write.
+    pub fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        self.send_bytes(data, "write_data")
+    }
+
+    /// Sets the position of the cursor.
+    ///
+    /// # Arguments
+    /// * `idx_row` - Row index.
+    /// * `idx_col` - Column index.
+    ///
+    /// # Errors
+    /// * Returns an argument range error, or a transport error if the write fails.
+    pub fn set_pos(&mut self, idx_row: u8, idx_col: u8) -> Result<()> {
+        Self::check_pos(idx_row, idx_col)?;
+        let first_digit = idx_col % 10;
+        let second_digit = idx_col / 10;
+        let str_to_send = &[ESC, BRACKET, idx_row + b'0', b';', second_digit + b'0', first_digit + b'0', CURSOR_POS_CMD];
+        self.send_bytes(str_to_send, "set_pos")
+    }
+
+    /// Builds the array format to be sent to the LCD for a user-defined character.
+    ///
+    /// # Arguments
+    /// * `str_user_def` - The user-defined character data (8 bytes, one per row).
+    /// * `cmd_str` - The output command buffer (Vec<u8> or &mut Vec<u8> recommended).
+    pub fn build_user_def_char(&self, str_user_def: &[u8], cmd_str: &mut Vec<u8>) {
+        // Each byte is converted to a string like "0xNN;" and appended as ASCII bytes
+        for &val in str_user_def.iter().take(8) {
+            // Format as uppercase hex, always two digits
+            let hex = format!("0x{:02X};", val);
+            cmd_str.extend_from_slice(hex.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::peripheral::transport::MemoryTransport;
+
+    /// A transport whose every write fails.
+    struct Broken;
+
+    impl Transport for Broken {
+        type Error = io::Error;
+
+        fn write(&mut self, _bytes: &[u8]) -> std::result::Result<(), Self::Error> {
+            Err(io::Error::other("bus down"))
+        }
+    }
+
+    /// A transport that records when each write arrived.
+    #[derive(Default)]
+    struct Timed {
+        writes: Vec<(Instant, Vec<u8>)>,
+    }
+
+    impl Transport for Timed {
+        type Error = io::Error;
+
+        fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), Self::Error> {
+            self.writes.push((Instant::now(), bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    impl Timed {
+        /// Returns the time between the writes at `first` and `first + 1`.
+        fn gap(&self, first: usize) -> Duration {
+            self.writes[first + 1].0 - self.writes[first].0
+        }
+    }
+
+    const DELAYS: CommandDelays = CommandDelays {
+        reset: Duration::from_millis(40),
+        clear: Duration::from_millis(20),
+        eeprom: Duration::from_millis(30),
+    };
+
+    fn lcds() -> Lcds<MemoryTransport> {
+        Lcds::new(MemoryTransport::new())
+    }
+
+    #[test]
+    fn argument_errors_are_caught_before_sending() {
+        let mut lcds = lcds();
+        assert!(matches!(lcds.write_string_at_pos(3, 0, "x"), Err(LcdsError::RowRange(3))));
+        assert!(matches!(lcds.set_pos(0, 40), Err(LcdsError::ColRange(40))));
+        assert!(matches!(lcds.erase_in_line(3), Err(LcdsError::EraseOptions(3))));
+        assert!(matches!(lcds.save_br(7), Err(LcdsError::BaudRateRange(7))));
+        assert!(matches!(lcds.chars_to_lcd(4), Err(LcdsError::TableRange(4))));
+        assert!(matches!(lcds.save_comm_to_eeprom(8), Err(LcdsError::CommRange(8))));
+        assert!(matches!(lcds.save_cursor_to_eeprom(3), Err(LcdsError::CursorRange(3))));
+        assert!(matches!(lcds.save_display_to_eeprom(2), Err(LcdsError::DisplayRange(2))));
+        assert!(matches!(lcds.define_user_char(&[0; 8], 8), Err(LcdsError::PositionRange(8))));
+        assert!(matches!(lcds.save_twi_addr(0x80), Err(LcdsError::TwiAddress(0x80))));
+        assert!(lcds.transport().transfers().is_empty());
+    }
+
+    #[test]
+    fn transport_failures_are_returned_with_their_source() {
+        let mut lcds = Lcds::new(Broken);
+        let err = lcds.display_clear().unwrap_err();
+        assert!(matches!(err, LcdsError::Transport(_)));
+        assert_eq!(err.code(), None);
+        assert_eq!(err.source().unwrap().to_string(), "bus down");
+        // Argument errors still win over a dead bus.
+        assert!(matches!(lcds.set_pos(5, 0), Err(LcdsError::RowRange(5))));
+    }
+
+    #[test]
+    fn codes_match_the_reference_library() {
+        let cases = [
+            (LcdsError::RowRange(0), Some(1)),
+            (LcdsError::ColRange(0), Some(2)),
+            (LcdsError::EraseOptions(0), Some(3)),
+            (LcdsError::BaudRateRange(0), Some(4)),
+            (LcdsError::TableRange(0), Some(5)),
+            (LcdsError::CommRange(0), Some(6)),
+            (LcdsError::CursorRange(0), Some(7)),
+            (LcdsError::DisplayRange(0), Some(8)),
+            (LcdsError::PositionRange(0), Some(9)),
+            (LcdsError::TwiAddress(0), None),
+            (LcdsError::ClockSpeed(0), None),
+            (LcdsError::SpiMode(Mode::Mode1), None),
+            (LcdsError::UnknownField(String::new()), None),
+            (LcdsError::Transport(Box::new(io::Error::other("x"))), None),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.code(), code, "{}", err);
+        }
+    }
+
+    #[test]
+    fn builder_rejects_settings_the_display_cannot_use() {
+        assert!(matches!(LcdsBuilder::new().clock_speed(0).build(), Err(LcdsError::ClockSpeed(0))));
+        let too_fast = PAR_SPD_MAX + 1;
+        assert!(matches!(
+            LcdsBuilder::new().clock_speed(too_fast).build(),
+            Err(LcdsError::ClockSpeed(v)) if v == too_fast
+        ));
+        for mode in [Mode::Mode1, Mode::Mode2, Mode::Mode3] {
+            assert!(matches!(LcdsBuilder::new().mode(mode).build(), Err(LcdsError::SpiMode(m)) if m == mode));
+        }
+    }
+
+    #[test]
+    fn valid_commands_send_the_reference_bytes() {
+        let mut lcds = lcds();
+        lcds.save_comm_to_eeprom(7).unwrap();
+        lcds.define_user_char(&[0x1F; 8], 2).unwrap();
+        let mut expected = b"\x1b[7m\x1b[".to_vec();
+        expected.extend_from_slice(&b"0x1F;".repeat(8));
+        expected.extend_from_slice(b"2d\x1b[3p");
+        assert_eq!(lcds.transport().bytes(), expected);
+    }
+
+    #[test]
+    fn batch_goes_out_as_one_write() {
+        let mut lcds = Lcds::new(Timed::default());
+        lcds.batch(|lcds| {
+            lcds.set_pos(1, 2)?;
+            lcds.write_data(b"42%")?;
+            // Nested batches join the outer one.
+            lcds.batch(|lcds| lcds.cursor_mode_set(false, false))
+        })
+        .unwrap();
+        let writes = &lcds.transport().writes;
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].1, b"\x1b[1;02H42%\x1b[0c");
+        assert_eq!(lcds.transactions(), 1);
+        assert_eq!(lcds.bytes_sent(), writes[0].1.len() as u64);
+    }
+
+    #[test]
+    fn failed_batch_still_sends_what_it_collected() {
+        let mut lcds = lcds();
+        let result = lcds.batch(|lcds| {
+            lcds.write_data(b"ok")?;
+            lcds.set_pos(9, 0)
+        });
+        assert!(matches!(result, Err(LcdsError::RowRange(9))));
+        assert_eq!(lcds.transport().transfers(), [b"ok".to_vec()]);
+    }
+
+    #[test]
+    fn slow_command_ends_the_batch_and_delays_the_rest() {
+        let mut lcds = Lcds::new(Timed::default()).with_delays(DELAYS);
+        lcds.batch(|lcds| {
+            lcds.display_clear()?;
+            lcds.write_data(b"Hi")
+        })
+        .unwrap();
+        let transport = lcds.transport();
+        assert_eq!(transport.writes.len(), 2);
+        assert_eq!(transport.writes[0].1, b"\x1b[j");
+        assert_eq!(transport.writes[1].1, b"Hi");
+        assert!(transport.gap(0) >= DELAYS.clear, "{:?}", transport.gap(0));
+    }
+
+    #[test]
+    fn each_slow_command_waits_for_its_own_delay() {
+        let mut lcds = Lcds::new(Timed::default()).with_delays(DELAYS);
+        lcds.reset().unwrap();
+        lcds.eeprom_wr_en().unwrap();
+        lcds.save_cursor_to_eeprom(1).unwrap();
+        lcds.write_data(b"x").unwrap();
+        let transport = lcds.transport();
+        assert!(transport.gap(0) >= DELAYS.reset, "{:?}", transport.gap(0));
+        assert!(transport.gap(2) >= DELAYS.eeprom, "{:?}", transport.gap(2));
+    }
+
+    #[test]
+    fn wait_ready_sleeps_out_a_pending_delay() {
+        let mut lcds = Lcds::new(Timed::default()).with_delays(DELAYS);
+        lcds.reset().unwrap();
+        let sent = lcds.transport().writes[0].0;
+        lcds.wait_ready();
+        assert!(sent.elapsed() >= DELAYS.reset);
+    }
+
+    #[test]
+    fn device_label_lets_several_displays_share_a_registry() {
+        let registry = Registry::new();
+        lcds().register_metrics(&registry).unwrap();
+        lcds().with_device("spidev0.1").register_metrics(&registry).unwrap();
+        assert!(lcds().register_metrics(&registry).is_err());
+        let labels: Vec<_> = registry.gather()[0]
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].get_value().to_string())
+            .collect();
+        assert_eq!(labels, [DEFAULT_DEVICE, "spidev0.1"]);
+    }
+}
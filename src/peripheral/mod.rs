@@ -0,0 +1,9 @@
+pub mod charset;
+pub mod eeprom;
+pub mod emulator;
+pub mod i2c;
+pub mod input;
+pub mod layout;
+pub mod lcds;
+pub mod transport;
+pub mod uart;
@@ -0,0 +1,81 @@
+use std::convert::Infallible;
+use std::error::Error;
+
+use rppal::spi::Spi;
+
+/// A byte sink the LCDS driver pushes its command and data bytes into.
+///
+/// The PmodCLS accepts the same escape sequences over SPI, TWI and UART, so
+/// the driver only needs somewhere to write bytes to. Implement this for any
+/// bus (or for a buffer in memory) to drive the display through it.
+pub trait Transport {
+    /// The error reported by the underlying bus when a write fails.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Writes every byte in `bytes` to the display as a single transfer.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl Transport for Spi {
+    type Error = rppal::spi::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        Spi::write(self, bytes).map(|_| ())
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    type Error = T::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        (**self).write(bytes)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    type Error = T::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        (**self).write(bytes)
+    }
+}
+
+/// A transport that records every transfer in memory instead of sending it.
+///
+/// Useful for running the driver without a Pi attached, e.g. to check the
+/// exact bytes a command produces.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryTransport {
+    transfers: Vec<Vec<u8>>,
+}
+
+impl MemoryTransport {
+    /// Creates an empty in-memory transport.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns each transfer written so far, in order.
+    pub fn transfers(&self) -> &[Vec<u8>] {
+        &self.transfers
+    }
+
+    /// Returns all written bytes concatenated into one buffer.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.transfers.concat()
+    }
+
+    /// Discards everything recorded so far.
+    pub fn clear(&mut self) {
+        self.transfers.clear();
+    }
+}
+
+impl Transport for MemoryTransport {
+    type Error = Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.transfers.push(bytes.to_vec());
+        Ok(())
+    }
+}
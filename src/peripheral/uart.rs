@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use log::info;
+use rppal::uart::{Parity, Uart};
+
+use super::lcds::{Lcds, LcdsError, Result};
+use super::transport::Transport;
+
+/// Baud rates selected by the `save_br` index (0-6), from the `[n]b`
+/// command table of the PmodCLS reference manual.
+///
+/// The host port must support the rate as well. Linux termios has no 76800
+/// setting, so `switch_baud_rate` rejects index 5 on a Pi.
+pub const BAUD_RATES: [u32; 7] = [2400, 4800, 9600, 19200, 38400, 76800, 115_200];
+
+/// Baud rate the PmodCLS uses out of the box (MD2..MD0 = 0,1,0).
+pub const UART_DEFAULT_BAUD: u32 = 9600;
+
+/// Pattern `switch_baud_rate` writes on row 0 for a person to look at.
+///
+/// The PmodCLS has no way to answer over UART, so nothing is read back: a
+/// garbled or missing pattern is the only sign the switch went wrong.
+pub const CHECK_PATTERN: &str = "0123456789ABCDEF";
+
+/// Returns the baud rate for a `save_br` index, or `None` if out of range.
+pub fn baud_rate_for_index(index: u8) -> Option<u32> {
+    BAUD_RATES.get(index as usize).copied()
+}
+
+/// Drives the PmodCLS over a serial port using 8N1 framing.
+#[derive(Debug)]
+pub struct UartTransport {
+    uart: Uart,
+}
+
+impl UartTransport {
+    /// Opens the Pi's primary UART at the given baud rate.
+    pub fn open(baud_rate: u32) -> std::result::Result<Self, rppal::uart::Error> {
+        Self::from_uart(Uart::new(baud_rate, Parity::None, 8, 1)?)
+    }
+
+    /// Opens a serial device by path (e.g., `/dev/ttyUSB0` or a pty).
+    pub fn open_path<P: AsRef<Path>>(path: P, baud_rate: u32) -> std::result::Result<Self, rppal::uart::Error> {
+        Self::from_uart(Uart::with_path(path, baud_rate, Parity::None, 8, 1)?)
+    }
+
+    /// Wraps an already configured UART and switches it to blocking writes,
+    /// so a whole command is queued before `write` returns.
+    pub fn from_uart(mut uart: Uart) -> std::result::Result<Self, rppal::uart::Error> {
+        uart.set_write_mode(true)?;
+        Ok(Self { uart })
+    }
+
+    /// Returns the baud rate the port is currently running at.
+    pub fn baud_rate(&self) -> u32 {
+        self.uart.baud_rate()
+    }
+
+    /// Reopens the port at a new baud rate.
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> std::result::Result<(), rppal::uart::Error> {
+        self.uart.drain()?;
+        self.uart.set_baud_rate(baud_rate)
+    }
+}
+
+impl Transport for UartTransport {
+    type Error = rppal::uart::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> std::result::Result<(), Self::Error> {
+        let mut written = 0;
+        while written < bytes.len() {
+            written += self.uart.write(&bytes[written..])?;
+        }
+        Ok(())
+    }
+}
+
+impl Lcds<UartTransport> {
+    /// Stores a new baud rate in the display's EEPROM, resets it and reopens
+    /// the port at the new speed, then writes `CHECK_PATTERN` on row 0.
+    ///
+    /// The port is first tried at the new rate, so a rate the host cannot run
+    /// at is rejected before anything reaches the display. The switch on the
+    /// display side is not verified: `Ok` only means every write went out,
+    /// and the pattern is there for a person to check by eye.
+    ///
+    /// The display only picks up the stored rate when its comm mode is set to
+    /// "UART, baud rate in EEPROM" (see `save_comm_to_eeprom(3)`).
+    ///
+    /// # Arguments
+    /// * `baud_rate` - The baud rate index (0-6), see `BAUD_RATES`.
+    ///
+    /// # Errors
+    /// * Returns `BaudRateRange` for an invalid index or a rate the port does
+    ///   not support, or a transport error if any write or the port
+    ///   reconfiguration fails.
+    pub fn switch_baud_rate(&mut self, baud_rate: u8) -> Result<()> {
+        let new_rate = baud_rate_for_index(baud_rate).ok_or(LcdsError::BaudRateRange(baud_rate))?;
+        let old_rate = self.transport().baud_rate();
+        match self.transport_mut().set_baud_rate(new_rate) {
+            Ok(()) => {}
+            Err(rppal::uart::Error::InvalidValue) => return Err(LcdsError::BaudRateRange(baud_rate)),
+            Err(e) => return Err(LcdsError::Transport(Box::new(e))),
+        }
+        self.transport_mut()
+            .set_baud_rate(old_rate)
+            .map_err(|e| LcdsError::Transport(Box::new(e)))?;
+
+        self.eeprom_wr_en()?;
+        self.save_br(baud_rate)?;
+        self.reset()?;
+        self.wait_ready();
+        self.transport_mut()
+            .set_baud_rate(new_rate)
+            .map_err(|e| LcdsError::Transport(Box::new(e)))?;
+        info!("UART switched to {} baud", new_rate);
+        self.display_clear()?;
+        self.write_string_at_pos(0, 0, CHECK_PATTERN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_map_to_the_manual_rates() {
+        let rates: Vec<_> = (0..7).map(|i| baud_rate_for_index(i).unwrap()).collect();
+        assert_eq!(rates, [2400, 4800, 9600, 19200, 38400, 76800, 115_200]);
+        assert_eq!(baud_rate_for_index(2), Some(UART_DEFAULT_BAUD));
+    }
+
+    #[test]
+    fn indices_past_the_table_are_rejected() {
+        assert_eq!(baud_rate_for_index(7), None);
+        assert_eq!(baud_rate_for_index(u8::MAX), None);
+    }
+}
@@ -0,0 +1,15 @@
+/// Computes the CRC-32 (IEEE 802.3) checksum of `data`.
+///
+/// Bitwise rather than table-driven: records are a few dozen bytes, so the
+/// 1 KiB table is not worth its space.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,3 @@
+pub mod crc;
+pub mod record_log;
+pub mod settings;
@@ -0,0 +1,365 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use log::{debug, info};
+
+use super::crc::crc32;
+use crate::peripheral::eeprom::{Eeprom25aa1024, EepromBus, EepromError, PAGE_SIZE, SIZE};
+
+/// Size of a record on the chip. A divisor of the page size, so records
+/// never straddle a page.
+pub const RECORD_SIZE: usize = 32;
+
+/// Application bytes carried by each record.
+pub const PAYLOAD_SIZE: usize = RECORD_SIZE - HEADER_SIZE - CRC_SIZE;
+
+const HEADER_SIZE: usize = 12; // seq (4) + timestamp (8)
+const CRC_SIZE: usize = 4;
+
+/// Records read per transfer while scanning.
+const SCAN_CHUNK: usize = PAGE_SIZE as usize / RECORD_SIZE;
+
+/// Errors reported by the record log.
+#[derive(Debug)]
+pub enum LogError {
+    /// The region is empty, outside the chip or not aligned to `RECORD_SIZE`.
+    Region(Range<u32>),
+    /// The EEPROM failed to read or write.
+    Eeprom(EepromError),
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::Region(r) => write!(
+                f,
+                "region {:#07X}..{:#07X} must be non-empty, within the chip and aligned to {} bytes",
+                r.start, r.end, RECORD_SIZE
+            ),
+            LogError::Eeprom(e) => write!(f, "record log: {}", e),
+        }
+    }
+}
+
+impl Error for LogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LogError::Eeprom(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EepromError> for LogError {
+    fn from(e: EepromError) -> Self {
+        LogError::Eeprom(e)
+    }
+}
+
+/// Result type returned by the record log.
+pub type Result<T> = std::result::Result<T, LogError>;
+
+/// One timestamped entry of the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    /// Position in the log, increasing by one per append.
+    pub seq: u32,
+    /// Caller-defined time, typically Unix seconds.
+    pub timestamp: u64,
+    pub payload: [u8; PAYLOAD_SIZE],
+}
+
+impl Record {
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut out = [0u8; RECORD_SIZE];
+        out[..4].copy_from_slice(&self.seq.to_le_bytes());
+        out[4..HEADER_SIZE].copy_from_slice(&self.timestamp.to_le_bytes());
+        out[HEADER_SIZE..RECORD_SIZE - CRC_SIZE].copy_from_slice(&self.payload);
+        let crc = crc32(&out[..RECORD_SIZE - CRC_SIZE]);
+        out[RECORD_SIZE - CRC_SIZE..].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// Decodes a slot, returning `None` if it is blank or fails its CRC.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (body, crc) = bytes.split_at(RECORD_SIZE - CRC_SIZE);
+        if crc32(body) != u32::from_le_bytes(crc.try_into().unwrap()) {
+            return None;
+        }
+        Some(Self {
+            seq: u32::from_le_bytes(body[..4].try_into().unwrap()),
+            timestamp: u64::from_le_bytes(body[4..HEADER_SIZE].try_into().unwrap()),
+            payload: body[HEADER_SIZE..].try_into().unwrap(),
+        })
+    }
+}
+
+/// A circular, append-only log of fixed-size records in an EEPROM region.
+///
+/// Each record carries a sequence number and a CRC, so a write cut short by
+/// power loss only ever invalidates the record being written. `open` finds
+/// the newest valid record by scanning the region; appends continue after
+/// it and overwrite the oldest record once the region is full.
+///
+/// Like the display widgets, the log does not own the chip: every operation
+/// takes the driver, so other stores can share it.
+#[derive(Debug, Clone)]
+pub struct RecordLog {
+    start: u32,
+    slots: u32,
+    next_slot: u32,
+    next_seq: u32,
+    len: u32,
+}
+
+impl RecordLog {
+    /// Opens the log in `region`, recovering its state from the chip.
+    ///
+    /// # Errors
+    /// * Returns `Region` for an unusable region, or the EEPROM error if the
+    ///   scan fails.
+    pub fn open<B: EepromBus>(eeprom: &mut Eeprom25aa1024<B>, region: Range<u32>) -> Result<Self> {
+        let size = RECORD_SIZE as u32;
+        let aligned = region.start.is_multiple_of(size) && region.end.is_multiple_of(size);
+        if region.start >= region.end || region.end > SIZE || !aligned {
+            return Err(LogError::Region(region));
+        }
+        let mut log = Self {
+            start: region.start,
+            slots: (region.end - region.start) / size,
+            next_slot: 0,
+            next_seq: 0,
+            len: 0,
+        };
+
+        let mut newest: Option<(u32, u32)> = None;
+        let mut buffer = [0u8; SCAN_CHUNK * RECORD_SIZE];
+        let mut slot = 0;
+        while slot < log.slots {
+            let count = (log.slots - slot).min(SCAN_CHUNK as u32) as usize;
+            let chunk = &mut buffer[..count * RECORD_SIZE];
+            eeprom.read(log.slot_addr(slot), chunk)?;
+            for (i, bytes) in chunk.chunks_exact(RECORD_SIZE).enumerate() {
+                if let Some(record) = Record::decode(bytes) {
+                    log.len += 1;
+                    if newest.is_none_or(|(seq, _)| record.seq > seq) {
+                        newest = Some((record.seq, slot + i as u32));
+                    }
+                }
+            }
+            slot += count as u32;
+        }
+        if let Some((seq, slot)) = newest {
+            log.next_seq = seq.wrapping_add(1);
+            log.next_slot = (slot + 1) % log.slots;
+        }
+        info!(
+            "record log at {:#07X}: {} of {} slots valid, next seq {}",
+            log.start, log.len, log.slots, log.next_seq
+        );
+        Ok(log)
+    }
+
+    /// Returns the number of records the region holds when full.
+    pub fn capacity(&self) -> u32 {
+        self.slots
+    }
+
+    /// Returns the number of valid records.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns true if the log holds no records.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the sequence number the next append will get.
+    pub fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    /// Appends a record, overwriting the oldest one if the log is full.
+    /// Payloads shorter than `PAYLOAD_SIZE` are zero-padded, longer ones
+    /// truncated.
+    ///
+    /// # Errors
+    /// * Returns the EEPROM error if the write fails; the slot is then retried
+    ///   by the next append.
+    pub fn append<B: EepromBus>(
+        &mut self,
+        eeprom: &mut Eeprom25aa1024<B>,
+        timestamp: u64,
+        payload: &[u8],
+    ) -> Result<Record> {
+        let mut record = Record {
+            seq: self.next_seq,
+            timestamp,
+            payload: [0; PAYLOAD_SIZE],
+        };
+        let n = payload.len().min(PAYLOAD_SIZE);
+        record.payload[..n].copy_from_slice(&payload[..n]);
+
+        let overwrites = self.len == self.slots;
+        eeprom.write(self.slot_addr(self.next_slot), &record.encode())?;
+        debug!("record {} written to slot {}", record.seq, self.next_slot);
+        self.next_slot = (self.next_slot + 1) % self.slots;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        if !overwrites {
+            self.len += 1;
+        }
+        Ok(record)
+    }
+
+    /// Reads the newest record.
+    ///
+    /// # Errors
+    /// * Returns the EEPROM error if the read fails.
+    pub fn latest<B: EepromBus>(&self, eeprom: &mut Eeprom25aa1024<B>) -> Result<Option<Record>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let slot = (self.next_slot + self.slots - 1) % self.slots;
+        self.read_slot(eeprom, slot)
+    }
+
+    /// Returns an iterator over the records from oldest to newest.
+    ///
+    /// Slots that fail their CRC are skipped.
+    pub fn iter<'a, B: EepromBus>(&self, eeprom: &'a mut Eeprom25aa1024<B>) -> Iter<'a, B> {
+        Iter {
+            log: self.clone(),
+            eeprom,
+            offset: 0,
+        }
+    }
+
+    fn slot_addr(&self, slot: u32) -> u32 {
+        self.start + slot * RECORD_SIZE as u32
+    }
+
+    fn read_slot<B: EepromBus>(&self, eeprom: &mut Eeprom25aa1024<B>, slot: u32) -> Result<Option<Record>> {
+        let mut bytes = [0u8; RECORD_SIZE];
+        eeprom.read(self.slot_addr(slot), &mut bytes)?;
+        Ok(Record::decode(&bytes))
+    }
+}
+
+/// Iterator over the records of a `RecordLog`, oldest first.
+pub struct Iter<'a, B: EepromBus> {
+    log: RecordLog,
+    eeprom: &'a mut Eeprom25aa1024<B>,
+    offset: u32,
+}
+
+impl<B: EepromBus> Iterator for Iter<'_, B> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Once the log has wrapped, the oldest record sits in the slot the
+        // next append will overwrite.
+        while self.offset < self.log.slots {
+            let slot = (self.log.next_slot + self.offset) % self.log.slots;
+            self.offset += 1;
+            match self.log.read_slot(self.eeprom, slot) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => debug!("record log slot {} is blank or corrupt", slot),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::eeprom::MockEepromBus;
+
+    const REGION: Range<u32> = 0x1000..0x1000 + 8 * RECORD_SIZE as u32;
+
+    fn seqs(log: &RecordLog, eeprom: &mut Eeprom25aa1024<MockEepromBus>) -> Vec<u32> {
+        log.iter(eeprom).map(|r| r.unwrap().seq).collect()
+    }
+
+    fn fill(log: &mut RecordLog, eeprom: &mut Eeprom25aa1024<MockEepromBus>, count: u32) {
+        for i in 0..count {
+            log.append(eeprom, 1_700_000_000 + i as u64, &i.to_le_bytes()).unwrap();
+        }
+    }
+
+    #[test]
+    fn appends_read_back_in_order() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        assert!(log.is_empty());
+        assert_eq!(log.latest(&mut eeprom).unwrap(), None);
+        fill(&mut log, &mut eeprom, 3);
+
+        assert_eq!(seqs(&log, &mut eeprom), [0, 1, 2]);
+        let latest = log.latest(&mut eeprom).unwrap().unwrap();
+        assert_eq!((latest.seq, latest.timestamp), (2, 1_700_000_002));
+        assert_eq!(latest.payload[..4], 2u32.to_le_bytes());
+        assert!(latest.payload[4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn wraps_and_resumes_after_the_newest_record() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        fill(&mut log, &mut eeprom, 20);
+        assert_eq!(log.len(), 8);
+
+        // The newest record sits in the middle of the region.
+        let mut log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        assert_eq!((log.len(), log.next_seq()), (8, 20));
+        assert_eq!(seqs(&log, &mut eeprom), (12..20).collect::<Vec<_>>());
+
+        log.append(&mut eeprom, 0, b"next").unwrap();
+        assert_eq!(seqs(&log, &mut eeprom), (13..21).collect::<Vec<_>>());
+        // Record 20 replaced record 12 and nothing outside the region changed.
+        let memory = eeprom.bus().memory();
+        assert!(memory[..REGION.start as usize].iter().all(|&b| b == 0xFF));
+        assert!(memory[REGION.end as usize..REGION.end as usize + 64].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn corrupt_records_are_skipped() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        fill(&mut log, &mut eeprom, 5);
+        eeprom.bus_mut().memory_mut()[REGION.start as usize + 2 * RECORD_SIZE + 14] ^= 0x01;
+
+        let log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        assert_eq!(seqs(&log, &mut eeprom), [0, 1, 3, 4]);
+        assert_eq!(log.next_seq(), 5);
+    }
+
+    #[test]
+    fn torn_append_leaves_the_previous_record_newest() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        fill(&mut log, &mut eeprom, 10);
+        // Power fails half way through overwriting the oldest record.
+        eeprom.bus_mut().tear_next_write(RECORD_SIZE / 2);
+        log.append(&mut eeprom, 0, b"lost").unwrap();
+
+        let mut log = RecordLog::open(&mut eeprom, REGION).unwrap();
+        assert_eq!(log.latest(&mut eeprom).unwrap().unwrap().seq, 9);
+        assert_eq!(seqs(&log, &mut eeprom), (3..10).collect::<Vec<_>>());
+        assert_eq!(log.next_seq(), 10);
+
+        log.append(&mut eeprom, 0, b"kept").unwrap();
+        assert_eq!(seqs(&log, &mut eeprom), (3..11).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unusable_regions_are_rejected() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        for region in [0..0, 16..64, 0..SIZE + RECORD_SIZE as u32] {
+            assert!(matches!(RecordLog::open(&mut eeprom, region), Err(LogError::Region(_))));
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use log::{debug, info, warn};
+
+use super::crc::crc32;
+use crate::peripheral::eeprom::{Eeprom25aa1024, EepromBus, EepromError, PAGE_SIZE, SIZE};
+
+/// Marker byte of a slot whose copy is complete.
+const VALID: u8 = 0x5A;
+/// Marker byte of a slot being written. Also what an erased byte reads as.
+const PENDING: u8 = 0xFF;
+
+const MAGIC: [u8; 2] = *b"KV";
+
+// Slot layout: marker (1), magic (2), version (1), generation (4),
+// body length (2), CRC of bytes 1..10 and the body (4), body.
+const HEADER_SIZE: usize = 14;
+
+/// Largest serialized body a slot can hold.
+pub const MAX_BODY: usize = PAGE_SIZE as usize - HEADER_SIZE;
+
+/// Errors reported by the settings store.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The region is not at least two whole pages within the chip.
+    Region(Range<u32>),
+    /// The settings serialize to more than `MAX_BODY` bytes.
+    TooLarge(usize),
+    /// A key or value is longer than 255 bytes.
+    EntryTooLong(String),
+    /// The EEPROM failed to read or write.
+    Eeprom(EepromError),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Region(r) => write!(
+                f,
+                "region {:#07X}..{:#07X} must be at least two pages, page-aligned and within the chip",
+                r.start, r.end
+            ),
+            SettingsError::TooLarge(n) => write!(f, "settings take {} bytes, at most {} fit", n, MAX_BODY),
+            SettingsError::EntryTooLong(key) => write!(f, "setting {:?} is longer than 255 bytes", key),
+            SettingsError::Eeprom(e) => write!(f, "settings store: {}", e),
+        }
+    }
+}
+
+impl Error for SettingsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SettingsError::Eeprom(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EepromError> for SettingsError {
+    fn from(e: EepromError) -> Self {
+        SettingsError::Eeprom(e)
+    }
+}
+
+/// Result type returned by the settings store.
+pub type Result<T> = std::result::Result<T, SettingsError>;
+
+/// A stored setting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    fn tag(&self) -> u8 {
+        match self {
+            Value::Bool(_) => 0,
+            Value::U32(_) => 1,
+            Value::I32(_) => 2,
+            Value::F32(_) => 3,
+            Value::Str(_) => 4,
+            Value::Bytes(_) => 5,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Value::Bool(v) => vec![*v as u8],
+            Value::U32(v) => v.to_le_bytes().to_vec(),
+            Value::I32(v) => v.to_le_bytes().to_vec(),
+            Value::F32(v) => v.to_le_bytes().to_vec(),
+            Value::Str(v) => v.as_bytes().to_vec(),
+            Value::Bytes(v) => v.clone(),
+        }
+    }
+
+    fn from_bytes(tag: u8, bytes: &[u8]) -> Option<Self> {
+        Some(match tag {
+            0 => Value::Bool(*bytes.first()? != 0),
+            1 => Value::U32(u32::from_le_bytes(bytes.try_into().ok()?)),
+            2 => Value::I32(i32::from_le_bytes(bytes.try_into().ok()?)),
+            3 => Value::F32(f32::from_le_bytes(bytes.try_into().ok()?)),
+            4 => Value::Str(String::from_utf8(bytes.to_vec()).ok()?),
+            5 => Value::Bytes(bytes.to_vec()),
+            _ => return None,
+        })
+    }
+}
+
+/// Types that can be stored as a setting.
+pub trait SettingValue: Sized {
+    /// Wraps the value for storing.
+    fn into_value(self) -> Value;
+
+    /// Unwraps a stored value, or returns `None` if it has another type.
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! setting_value {
+    ($ty:ty, $variant:ident) => {
+        impl SettingValue for $ty {
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) => Some(v.clone()),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+setting_value!(bool, Bool);
+setting_value!(u32, U32);
+setting_value!(i32, I32);
+setting_value!(f32, F32);
+setting_value!(String, Str);
+setting_value!(Vec<u8>, Bytes);
+
+/// Upgrades settings stored under an older schema version in place.
+/// Called with the stored version.
+pub type Migration = fn(from: u8, entries: &mut BTreeMap<String, Value>);
+
+/// A small key/value store for settings that must survive reboots.
+///
+/// The whole map is written as one copy per page, and each update goes to
+/// the next page of the region in turn, which spreads wear evenly. A copy is
+/// written with a pending marker that is flipped to valid only once the
+/// copy is complete, and carries a generation number and a CRC; `open` loads
+/// the newest valid copy, so a torn update leaves the previous one in force.
+#[derive(Debug, Clone)]
+pub struct SettingsStore {
+    start: u32,
+    slots: u32,
+    next_slot: u32,
+    generation: u32,
+    version: u8,
+    entries: BTreeMap<String, Value>,
+}
+
+impl SettingsStore {
+    /// Opens the store in `region` for schema `version`.
+    ///
+    /// If the newest copy was written under an older version, `migrate` is
+    /// called on its entries and the result is written back at `version`.
+    ///
+    /// # Errors
+    /// * Returns `Region` for an unusable region, or the EEPROM error if a
+    ///   read or the migrated write fails.
+    pub fn open<B: EepromBus>(
+        eeprom: &mut Eeprom25aa1024<B>,
+        region: Range<u32>,
+        version: u8,
+        migrate: Migration,
+    ) -> Result<Self> {
+        let aligned = region.start.is_multiple_of(PAGE_SIZE) && region.end.is_multiple_of(PAGE_SIZE);
+        if !aligned || region.end > SIZE || region.end < region.start.saturating_add(2 * PAGE_SIZE) {
+            return Err(SettingsError::Region(region));
+        }
+        let mut store = Self {
+            start: region.start,
+            slots: (region.end - region.start) / PAGE_SIZE,
+            next_slot: 0,
+            generation: 0,
+            version,
+            entries: BTreeMap::new(),
+        };
+
+        let mut newest: Option<(u32, u32, u8, BTreeMap<String, Value>)> = None;
+        let mut page = [0u8; PAGE_SIZE as usize];
+        for slot in 0..store.slots {
+            eeprom.read(store.slot_addr(slot), &mut page)?;
+            let Some((generation, stored_version, entries)) = decode(&page) else {
+                continue;
+            };
+            if newest.as_ref().is_none_or(|n| generation > n.0) {
+                newest = Some((generation, slot, stored_version, entries));
+            }
+        }
+
+        if let Some((generation, slot, stored_version, entries)) = newest {
+            store.generation = generation;
+            store.next_slot = (slot + 1) % store.slots;
+            store.entries = entries;
+            info!(
+                "settings at {:#07X}: generation {}, {} entries, version {}",
+                store.start,
+                generation,
+                store.entries.len(),
+                stored_version
+            );
+            if stored_version != version {
+                info!("migrating settings from version {} to {}", stored_version, version);
+                migrate(stored_version, &mut store.entries);
+                store.commit(eeprom)?;
+            }
+        } else {
+            info!("settings at {:#07X}: no valid copy, starting empty", store.start);
+        }
+        Ok(store)
+    }
+
+    /// Returns the schema version the store writes.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the value of `key` if it is set and of type `V`.
+    pub fn get<V: SettingValue>(&self, key: &str) -> Option<V> {
+        self.entries.get(key).and_then(V::from_value)
+    }
+
+    /// Returns the raw value of `key`.
+    pub fn get_value(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    /// Returns the keys in order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Sets `key` and writes the settings back.
+    ///
+    /// # Errors
+    /// * Returns `EntryTooLong` or `TooLarge` if the settings no longer fit,
+    ///   or the EEPROM error if the write fails. The settings are left
+    ///   unchanged either way.
+    pub fn set<B: EepromBus, V: SettingValue>(
+        &mut self,
+        eeprom: &mut Eeprom25aa1024<B>,
+        key: &str,
+        value: V,
+    ) -> Result<()> {
+        let value = value.into_value();
+        if self.entries.get(key) == Some(&value) {
+            return Ok(());
+        }
+        let previous = self.entries.insert(key.to_string(), value);
+        if let Err(e) = self.commit(eeprom) {
+            match previous {
+                Some(v) => self.entries.insert(key.to_string(), v),
+                None => self.entries.remove(key),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Removes `key` and writes the settings back. Returns true if it was set.
+    ///
+    /// # Errors
+    /// * Returns the EEPROM error if the write fails, leaving `key` set.
+    pub fn delete<B: EepromBus>(&mut self, eeprom: &mut Eeprom25aa1024<B>, key: &str) -> Result<bool> {
+        let Some(previous) = self.entries.remove(key) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.commit(eeprom) {
+            self.entries.insert(key.to_string(), previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Writes a new copy to the next slot, then marks it valid. Nothing is
+    /// written if the entries do not encode.
+    fn commit<B: EepromBus>(&mut self, eeprom: &mut Eeprom25aa1024<B>) -> Result<()> {
+        let body = encode_body(&self.entries)?;
+        let generation = self.generation.wrapping_add(1);
+        let mut page = Vec::with_capacity(HEADER_SIZE + body.len());
+        page.push(PENDING);
+        page.extend_from_slice(&MAGIC);
+        page.push(self.version);
+        page.extend_from_slice(&generation.to_le_bytes());
+        page.extend_from_slice(&(body.len() as u16).to_le_bytes());
+        let crc = crc32(&[&page[1..], &body[..]].concat());
+        page.extend_from_slice(&crc.to_le_bytes());
+        page.extend_from_slice(&body);
+
+        let addr = self.slot_addr(self.next_slot);
+        eeprom.write(addr, &page)?;
+        eeprom.write(addr, &[VALID])?;
+        debug!("settings generation {} written to slot {}", generation, self.next_slot);
+        self.generation = generation;
+        self.next_slot = (self.next_slot + 1) % self.slots;
+        Ok(())
+    }
+
+    fn slot_addr(&self, slot: u32) -> u32 {
+        self.start + slot * PAGE_SIZE
+    }
+}
+
+fn encode_body(entries: &BTreeMap<String, Value>) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    for (key, value) in entries {
+        let bytes = value.to_bytes();
+        if key.len() > u8::MAX as usize || bytes.len() > u8::MAX as usize {
+            return Err(SettingsError::EntryTooLong(key.clone()));
+        }
+        body.push(key.len() as u8);
+        body.extend_from_slice(key.as_bytes());
+        body.push(value.tag());
+        body.push(bytes.len() as u8);
+        body.extend_from_slice(&bytes);
+    }
+    if body.len() > MAX_BODY {
+        return Err(SettingsError::TooLarge(body.len()));
+    }
+    Ok(body)
+}
+
+/// Decodes a slot, returning its generation, version and entries, or `None`
+/// if it is not a complete, intact copy.
+fn decode(page: &[u8]) -> Option<(u32, u8, BTreeMap<String, Value>)> {
+    if page[0] != VALID || page[1..3] != MAGIC {
+        return None;
+    }
+    let version = page[3];
+    let generation = u32::from_le_bytes(page[4..8].try_into().unwrap());
+    let len = u16::from_le_bytes(page[8..10].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(page[10..14].try_into().unwrap());
+    let body = page.get(HEADER_SIZE..HEADER_SIZE + len)?;
+    if crc32(&[&page[1..10], body].concat()) != crc {
+        warn!("settings copy of generation {} failed its CRC", generation);
+        return None;
+    }
+
+    let mut entries = BTreeMap::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let key_len = *rest.first()? as usize;
+        let key = std::str::from_utf8(rest.get(1..1 + key_len)?).ok()?;
+        let tag = *rest.get(1 + key_len)?;
+        let value_len = *rest.get(2 + key_len)? as usize;
+        let value = rest.get(3 + key_len..3 + key_len + value_len)?;
+        entries.insert(key.to_string(), Value::from_bytes(tag, value)?);
+        rest = &rest[3 + key_len + value_len..];
+    }
+    Some((generation, version, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::eeprom::{MockEepromBus, Protection};
+
+    const REGION: Range<u32> = 0x18000..0x18000 + 4 * PAGE_SIZE;
+
+    fn no_migration(_: u8, _: &mut BTreeMap<String, Value>) {}
+
+    fn open(eeprom: &mut Eeprom25aa1024<MockEepromBus>) -> SettingsStore {
+        SettingsStore::open(eeprom, REGION, 1, no_migration).unwrap()
+    }
+
+    #[test]
+    fn values_survive_a_reopen() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut store = open(&mut eeprom);
+        store.set(&mut eeprom, "threshold", 40u32).unwrap();
+        store.set(&mut eeprom, "name", "bench".to_string()).unwrap();
+        store.set(&mut eeprom, "offset", -1.5f32).unwrap();
+        store.set(&mut eeprom, "threshold", 45u32).unwrap();
+        assert!(store.delete(&mut eeprom, "offset").unwrap());
+
+        let store = open(&mut eeprom);
+        assert_eq!(store.get::<u32>("threshold"), Some(45));
+        assert_eq!(store.get::<String>("name").as_deref(), Some("bench"));
+        assert_eq!(store.get::<f32>("offset"), None);
+        assert_eq!(store.get::<bool>("threshold"), None, "wrong type");
+    }
+
+    #[test]
+    fn failed_writes_leave_memory_matching_the_chip() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut store = open(&mut eeprom);
+        store.set(&mut eeprom, "threshold", 40u32).unwrap();
+        eeprom.set_protection(Protection::UpperQuarter).unwrap();
+
+        let err = store.set(&mut eeprom, "threshold", 50u32).unwrap_err();
+        assert!(matches!(err, SettingsError::Eeprom(EepromError::Protected(_))));
+        assert!(store.set(&mut eeprom, "added", true).is_err());
+        assert!(store.delete(&mut eeprom, "threshold").is_err());
+        assert_eq!(store.get::<u32>("threshold"), Some(40));
+        assert_eq!(store.get::<bool>("added"), None);
+
+        eeprom.set_protection(Protection::None).unwrap();
+        let reopened = open(&mut eeprom);
+        assert_eq!(reopened.keys().collect::<Vec<_>>(), store.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn oversized_values_are_rejected_without_writing() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut store = open(&mut eeprom);
+        let err = store.set(&mut eeprom, "blob", vec![0u8; 300]).unwrap_err();
+        assert!(matches!(err, SettingsError::EntryTooLong(_)));
+        assert_eq!(store.keys().count(), 0);
+        assert!(eeprom.bus().memory()[REGION.start as usize..REGION.end as usize].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn torn_update_keeps_the_previous_copy() {
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut store = open(&mut eeprom);
+        store.set(&mut eeprom, "threshold", 40u32).unwrap();
+        eeprom.bus_mut().tear_next_write(HEADER_SIZE + 2);
+        store.set(&mut eeprom, "threshold", 50u32).unwrap();
+        // The copy was marked valid but its body is incomplete.
+        let store = open(&mut eeprom);
+        assert_eq!(store.get::<u32>("threshold"), Some(40));
+    }
+
+    #[test]
+    fn migration_runs_once() {
+        fn rename(from: u8, entries: &mut BTreeMap<String, Value>) {
+            assert_eq!(from, 1);
+            if let Some(v) = entries.remove("thresh") {
+                entries.insert("threshold".to_string(), v);
+            }
+        }
+        let mut eeprom = Eeprom25aa1024::new(MockEepromBus::new());
+        let mut store = open(&mut eeprom);
+        store.set(&mut eeprom, "thresh", 40u32).unwrap();
+
+        let store = SettingsStore::open(&mut eeprom, REGION, 2, rename).unwrap();
+        assert_eq!(store.get::<u32>("threshold"), Some(40));
+        let store = SettingsStore::open(&mut eeprom, REGION, 2, |_, _| panic!("already migrated")).unwrap();
+        assert_eq!(store.version(), 2);
+    }
+}